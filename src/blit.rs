@@ -0,0 +1,161 @@
+// The scene is drawn at CANVAS_SIDE square into an offscreen texture, then a second pass blows
+// it up onto the window
+
+use std::borrow::Cow;
+use glam::IVec2;
+
+use crate::constants::*;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scaling {
+    Integer, // Largest whole multiple that fits; crisp, but may leave a wide border
+    Aspect, // Fill the window's short side, nearest pixel; some rows come out thicker than others
+    Sharp, // Fill the short side, nearest pixel except blended at pixel edges; even, slightly soft
+}
+
+pub const SCALINGS:[(Scaling, &str); 3] = [
+    (Scaling::Integer, "integer"),
+    (Scaling::Aspect, "aspect"),
+    (Scaling::Sharp, "sharp"),
+];
+
+pub fn scaling_parse(name:&str) -> Option<Scaling> {
+    SCALINGS.iter().find(|&&(_, scaling_name)| scaling_name == name).map(|&(scaling, _)| scaling)
+}
+
+// Target the scene renders into. Copyable out, for screenshots
+pub fn make_canvas_texture(device:&wgpu::Device, format:wgpu::TextureFormat) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Canvas"),
+        size: wgpu::Extent3d { width:CANVAS_SIDE, height:CANVAS_SIDE, depth_or_array_layers:1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+// Pipeline drawing the canvas to a target of the given format, its bind group, and the uniform
+// buffer blit_uniform's output goes in. Draw with the quad root buffers
+pub fn make_blit_pipeline(device:&wgpu::Device, format:wgpu::TextureFormat, canvas_view:&wgpu::TextureView, root_vertex_layout:wgpu::VertexBufferLayout<'static>) -> (wgpu::RenderPipeline, wgpu::BindGroup, wgpu::Buffer) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Blit"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("blit.wgsl"))),
+    });
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Blit bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    });
+
+    // Linear, but fs_blit samples texel centers except where it wants blending
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Blit sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..wgpu::SamplerDescriptor::default()
+    });
+
+    let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Blit uniform"),
+        size: std::mem::size_of::<[f32; 8]>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Blit bind group"),
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(canvas_view) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&sampler) },
+            wgpu::BindGroupEntry { binding: 2, resource: uniform.as_entire_binding() },
+        ],
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Blit pipeline"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Blit"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_blit",
+            buffers: &[root_vertex_layout],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_blit",
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Cw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..wgpu::PrimitiveState::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    (pipeline, bind_group, uniform)
+}
+
+// Where the canvas goes in a window of this size (top left, size), centered
+pub fn blit_rect(window:IVec2, scaling:Scaling) -> (IVec2, IVec2) {
+    let canvas = CANVAS_SIDE as i32;
+    let short = window.min_element().max(1);
+    let side = match scaling {
+        Scaling::Integer if short >= canvas => short - short % canvas,
+        _ => short // Window smaller than the canvas has to shrink it anyway
+    };
+    ((window - side)/2, IVec2::splat(side))
+}
+
+// Contents for the uniform buffer, matching blit.wgsl's Blit
+pub fn blit_uniform(rect_size:IVec2, scaling:Scaling, crt:bool) -> [f32; 8] {
+    let canvas = CANVAS_SIDE as f32;
+    [
+        canvas, canvas,
+        rect_size.x as f32, rect_size.y as f32,
+        if scaling == Scaling::Sharp { 1. } else { 0. },
+        if crt { 1. } else { 0. },
+        0., 0. // Padding
+    ]
+}
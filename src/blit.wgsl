@@ -0,0 +1,56 @@
+// Upscaling the canvas to the window
+
+struct Blit {
+    source_size: vec2<f32>, // Canvas, in pixels
+    dest_size: vec2<f32>, // Where it lands in the window, in pixels
+    sharp: f32, // 1 for sharp bilinear, 0 for nearest
+    crt: f32, // 1 for scanlines and vignette
+};
+
+struct VertexOutput {
+    @location(0) uv: vec2<f32>,
+    @builtin(position) position: vec4<f32>,
+};
+
+@group(0)
+@binding(0)
+var canvas: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var canvas_sampler: sampler;
+
+@group(0)
+@binding(2)
+var<uniform> blit: Blit;
+
+// One quad over the whole viewport
+@vertex
+fn vs_blit(@location(0) position: vec2<f32>) -> VertexOutput {
+    var result: VertexOutput;
+    result.uv = position;
+    let full_position = position*2. - vec2(1.,1.);
+    result.position = vec4(full_position.x, -full_position.y, 0., 1.);
+    return result;
+}
+
+@fragment
+fn fs_blit(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = vertex.uv*blit.source_size;
+    var uv = (floor(texel) + 0.5)/blit.source_size; // Nearest
+    if blit.sharp > 0.5 {
+        // Nearest inside each canvas pixel, blending only across the last output pixel at its edge
+        let scale = max(blit.dest_size/blit.source_size, vec2(1.,1.));
+        let center_dist = fract(texel) - 0.5;
+        let region = 0.5 - 0.5/scale;
+        let f = (center_dist - clamp(center_dist, -region, region))*scale + 0.5;
+        uv = (floor(texel) + f)/blit.source_size;
+    }
+    var color = textureSampleLevel(canvas, canvas_sampler, uv, 0.).rgb;
+    if blit.crt > 0.5 {
+        let scan = sin(fract(texel.y)*3.14159265); // Brightest down the middle of each canvas row
+        let edge = vertex.uv - 0.5;
+        color *= mix(0.7, 1., scan) * (1. - 0.6*dot(edge, edge));
+    }
+    return vec4<f32>(color, 1.0);
+}
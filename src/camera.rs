@@ -0,0 +1,57 @@
+// Scrolling view for rooms bigger than the canvas. Works in room pixels: wall tile (x,y) covers
+// x*TILE_SIDE..(x+1)*TILE_SIDE, so cell (x,y) is centered on (x+1,y+1)*TILE_SIDE
+
+use crate::constants::*;
+use crate::room::*;
+
+use glam::Vec2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+	pub center: Vec2, // Room pixel shown at the middle of the canvas
+	pub dead_zone: Vec2, // Player can get this far from center (each way) before the camera moves
+	pub ease: f32, // Fraction of the remaining distance covered each step; 1 snaps straight there
+}
+
+// Starts on the player, with no easing in
+pub fn camera_make(room:&Room, dead_zone:Vec2, ease:f32) -> Camera {
+	Camera { center:camera_clamp(room, camera_target(room)), dead_zone, ease }
+}
+
+// Middle of the player's cell, or the middle of the room if there's no player
+fn camera_target(room:&Room) -> Vec2 {
+	match room_player(room) {
+		Some(player) => (room.actors[player].1 + 1).as_vec2() * TILE_SIDE as f32,
+		None => room_extent(room)/2.
+	}
+}
+
+fn room_extent(room:&Room) -> Vec2 {
+	let (height, width) = room.walls.dim();
+	Vec2::new(width as f32, height as f32) * TILE_SIDE as f32
+}
+
+// Don't show past the edge of the room; on an axis where the whole room fits, center it
+fn camera_clamp(room:&Room, center:Vec2) -> Vec2 {
+	let extent = room_extent(room);
+	let half = CANVAS_SIDE as f32/2.;
+	let clamp = |center:f32, extent:f32| if extent <= half*2. { extent/2. } else { center.clamp(half, extent - half) };
+	Vec2::new(clamp(center.x, extent.x), clamp(center.y, extent.y))
+}
+
+// Move toward the player. Returns true if the camera moved, meaning another step is wanted
+pub fn camera_step(camera:&mut Camera, room:&Room) -> bool {
+	let offset = camera_target(room) - camera.center;
+	let outside = (offset.abs() - camera.dead_zone).max(Vec2::ZERO);
+	let goal = camera_clamp(room, camera.center + outside * offset.signum());
+
+	let before = camera.center;
+	camera.center += (goal - camera.center) * camera.ease.clamp(0., 1.);
+	if (goal - camera.center).abs().max_element() < 0.5 { camera.center = goal; } // Close enough, stop
+	camera.center != before
+}
+
+// Canvas position of the room's top left corner. Whole pixels, so sprites don't shimmer while scrolling
+pub fn camera_origin(camera:&Camera) -> Vec2 {
+	(Vec2::splat(CANVAS_SIDE as f32/2.) - camera.center).round()
+}
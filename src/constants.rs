@@ -14,12 +14,14 @@ pub const MONSTER_X_ORIGIN:u32 = 64;
 pub const MONSTER_Y_ORIGIN:u32 = 0;
 pub const MONSTER_COUNT:u32 = 8;
 pub const TILE_Y_ORIGIN:u32 = 8;
+pub const KIND_Y_ORIGIN:u32 = 32; // Keys and gates of every kind
 
 pub const LARGEST_PNG_SIDE:u32 = TILE_SIDE;
 pub const CANVAS_SIDE:u32 = 128;
 
-pub const SPRITE_SIZE:u64 = 8*mem::size_of::<f32>() as u64;
-pub const SPRITES_MAX:u64 = 512; // 13*13*2 = 338, round up for room for bullets. (Realistically, 256 would be enough)
+pub const SPRITE_TINT_OFFSET:u64 = 9*mem::size_of::<f32>() as u64; // pos_basis, pos_size, tex_basis, tex_size, brightness as f32s
+pub const SPRITE_SIZE:u64 = SPRITE_TINT_OFFSET + mem::size_of::<u32>() as u64; // Then tint as a u32
+pub const SPRITES_MAX:u64 = 512; // Smallest instance buffer. 13*13*2 = 338, round up for room for bullets; bigger rooms grow it
 
 // Walls
 
@@ -33,6 +35,7 @@ pub enum Dir {
 }
 
 pub const DIR_COMPASS:[IVec2;4] = [IVec2::new(1,0), IVec2::new(0,1), IVec2::new(-1,0) , IVec2::new(0,-1)];
+pub const DIR_ALL:[Dir;4] = [Dir::Right, Dir::Down, Dir::Left, Dir::Up]; // Indexed same as DIR_COMPASS
 
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Eq, PartialEq, IntEnum)]
@@ -92,14 +95,46 @@ pub const WALL_ROT_SEMANTICS:[[u8;2];WallRot::Count as usize] = [
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Actor {
 	Player(Dir),
-	Door,
-	Key(bool), // True for rightward
+	Door, // Way out; takes a plain key
+	Key(bool, u8), // True for rightward; kind
+	Gate(u8), // Locked passage; takes a key of the same kind, never plain
 	Ammo,
-	Shot,
+	Shot(Dir),
 	Monster(Dir, u8) // Direction, sprite
 }
 
+pub const AMMO_SPAWN:u32 = 2; // Pickups per room at level 0
+pub const AMMO_PICKUP:u32 = 3; // Shots per pickup
+pub const KEY_SPAWN_MAX:u32 = 4;
+pub const KEY_KINDS:u32 = 4; // Plain, then red, green and blue, which are drawn with 1-3 pips
+pub const GATE_SPAWN_MAX:u32 = KEY_KINDS-1;
+pub const DOOR_SPAWN_MAX:u32 = 3;
+pub const DOOR_SCORE:u32 = 50;
+pub const MONSTER_SPAWN:u32 = 3; // Monsters per room at level 0
+pub const MONSTER_SPAWN_MAX:u32 = 12;
+pub const MONSTER_SPAWN_DISTANCE:i32 = 4; // Minimum manhattan distance from player at spawn
+pub const MONSTER_SCORE:u32 = 10;
+pub const GAME_RNG_STREAM:u64 = u64::MAX; // Streams below these are used for levels
+pub const ATLAS_RNG_STREAM:u64 = u64::MAX-1;
+pub const MONSTER_WANDER_TURN:f64 = 0.25; // Chance of turning at a junction when not chasing
+pub const ROOM_ATTEMPTS_MAX:u32 = 200; // Rooms generated looking for one that's solvable and in the difficulty band
+
+// Interface
+
+pub const HISTORY_DEPTH:usize = 1000; // Default number of undoable turns
+pub const CAMERA_DEAD_ZONE:f32 = 20.; // Pixels the player can stray from the middle of the screen before it scrolls
+pub const CAMERA_EASE:f32 = 0.25; // Share of the way the camera catches up each frame
+pub const FOG_REMEMBERED:f32 = 0.35; // Brightness of cells seen before but not now
+pub const TWEEN_DURATION:f64 = 0.08; // Seconds for actors to slide from one cell to the next
+pub const INPUT_QUEUE_MAX:usize = 4; // Keys pressed while actors are still sliding wait their turn; past this many, more are dropped
+pub const FRAME_INTERVAL:f64 = 1./60.; // Seconds between redraws while anything is moving
+pub const TICK_INTERVAL:f64 = 0.5; // In realtime mode, seconds the player can stand still before the world takes a turn anyway
+
 // Helpers
 
 pub fn ivec_to_index(v:IVec2) -> (usize, usize) { (v.y as usize, v.x as usize) }
 
+// Text files (replays, saves) spell directions with these
+pub const DIR_LETTER:[char;4] = ['R', 'D', 'L', 'U']; // Indexed same as DIR_ALL
+pub fn letter_to_dir(c:char) -> Option<Dir> { DIR_LETTER.iter().position(|&l| l == c).map(|idx| DIR_ALL[idx]) }
+
@@ -1,62 +1,206 @@
 // Gameplay mechanics
 
 use crate::constants::*;
+use crate::maze::*;
 use crate::room::*;
+use crate::slots::*;
 use glam::IVec2;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct GameState {
-    pub player_idx: usize,
-    keys: u32,
+    pub player: Handle,
+    pub keys: [u32; KEY_KINDS as usize], // Held, by kind
+    pub ammo: u32,
+    pub score: u32,
+    pub dead: bool,
+    pub level: u32,
+    pub seed: u64,
+    pub maze: MazeStyle, // For this and later levels
+    pub(crate) rng: ChaCha8Rng, // For monster decisions
+    exiting: bool, // Player stepped through a door this turn
+}
+impl GameState {
+    pub fn new(seed:u64) -> Self {
+        Self { player:Handle::NONE, keys:[0; KEY_KINDS as usize], ammo:0, score:0, dead:false, level:0, seed, maze:MazeStyle::default(), rng:game_rng(seed, GAME_RNG_STREAM), exiting:false }
+    }
 }
 impl Default for GameState {
-    fn default() -> Self { Self { player_idx:0, keys:0 } }
+    fn default() -> Self { Self::new(rand::random()) }
+}
+
+// ChaCha is used because its output is specified, unlike StdRng. Note also rand's
+// gen_range over usize differs between 32 and 64 bit, so only sample u32/i32 ranges.
+pub fn game_rng(seed:u64, stream:u64) -> ChaCha8Rng {
+	let mut rng = ChaCha8Rng::seed_from_u64(seed);
+	rng.set_stream(stream);
+	rng
+}
+
+// Generate the room for the current level and find the player in it.
+// Each level has its own stream, so rooms don't depend on how earlier levels were played
+pub fn game_room(state:&mut GameState) -> Room {
+	let spec = RoomSpec { maze:state.maze, ..RoomSpec::level(state.level) };
+	let room = room_make(&spec, &mut game_rng(state.seed, state.level as u64));
+	state.player = room_player(&room).expect("Room has no player");
+	room
 }
 
-pub fn game_collide(state:&mut GameState, from:&Actor, into:&Actor, at:IVec2) -> (bool, bool) { // Returns halt? delete?
+pub fn game_collide(state:&mut GameState, from:&Actor, into:&Actor, at:IVec2) -> (bool, bool, bool) { // Returns halt? delete into? delete from?
 	match (from, into) {
-		(Actor::Player(_), Actor::Key(_)) => {
-			state.keys += 1;
-			(false, true)
+		(Actor::Player(dir), Actor::Key(rightward, kind)) => {
+			if *dir == room_key_dir(*rightward) {
+				state.keys[*kind as usize] += 1;
+				(false, true, false)
+			} else {
+				(false, false, false) // Wrong way, walk over it
+			}
+		},
+		(Actor::Player(_), Actor::Ammo) => {
+			state.ammo += AMMO_PICKUP;
+			(false, true, false)
 		},
 		(Actor::Player(_), Actor::Door) => {
-			if state.keys > 0 {
-				state.keys -= 1;
-				(false, true)
+			if state.keys[0] > 0 {
+				state.keys[0] -= 1;
+				state.exiting = true;
+				(false, false, false)
+			} else {
+				(true, false, false)
+			}
+		},
+		(Actor::Player(_), Actor::Gate(kind)) => {
+			if state.keys[*kind as usize] > 0 { // Unlocks for good
+				state.keys[*kind as usize] -= 1;
+				(false, true, false)
 			} else {
-				(true, false)
+				(true, false, false)
 			}
 		},
-		_ => (false, false)
+		(Actor::Player(_), Actor::Monster(..)) | (Actor::Monster(..), Actor::Player(_)) => {
+			state.dead = true;
+			(false, false, false)
+		},
+		(Actor::Shot(_), Actor::Monster(..)) | (Actor::Monster(..), Actor::Shot(_)) => {
+			state.score += MONSTER_SCORE;
+			(true, true, true)
+		},
+		(Actor::Shot(_), _) => (true, false, true), // Shot stops on anything
+		(Actor::Monster(..), _) => (true, false, false),
+		_ => (false, false, false)
+	}
+}
+
+// Try to move actor one cell; returns true if it moved. May remove the actor
+fn game_step(state:&mut GameState, room:&mut Room, handle:Handle, dir:Dir) -> bool {
+	let (actor, at) = room.actors[handle];
+	if 0 == room.routes[ivec_to_index(at)] & (1 << dir as u8) { return false }
+	let want_at = at + DIR_COMPASS[dir as usize];
+
+	let targets:Vec<(Handle, Actor)> = room_actors_at(room, want_at).map(|(target, &into)| (target, into)).collect();
+	for (target, into) in targets {
+		if target == handle { continue }
+		let (halt, destroy_into, destroy_from) = game_collide(state, &actor, &into, want_at);
+		if destroy_into { room.actors.remove(target); }
+		if destroy_from { room.actors.remove(handle); return false }
+		if halt { return false }
+	}
+
+	room.actors[handle].1 = want_at;
+	true
+}
+
+// If target can be seen in a straight line down open passages, which way is it?
+fn game_sight(room:&Room, from:IVec2, to:IVec2) -> Option<Dir> {
+	for dir in DIR_ALL {
+		let mut at = from;
+		while 0 != room.routes[ivec_to_index(at)] & (1 << dir as u8) {
+			at += DIR_COMPASS[dir as usize];
+			if at == to { return Some(dir) }
+		}
+	}
+	None
+}
+
+// Keep going, occasionally turning at junctions; only reverse at dead ends
+fn game_wander(room:&Room, at:IVec2, dir:Dir, rng:&mut impl Rng) -> Dir {
+	let route = room.routes[ivec_to_index(at)];
+	let back = DIR_ALL[(dir as usize + 2) % 4];
+	let open:Vec<Dir> = DIR_ALL.iter().copied()
+		.filter(|&d| d != back && 0 != route & (1 << d as u8)).collect();
+
+	if open.is_empty() { return back }
+	if open.contains(&dir) && !rng.gen_bool(MONSTER_WANDER_TURN) { return dir }
+	*open.choose(rng).unwrap()
+}
+
+// Everything that happens after the player acts
+fn game_turn(state:&mut GameState, room:&mut Room) {
+	for handle in room.actors.handles() {
+		if let Some(&(Actor::Shot(dir), _)) = room.actors.get(handle) {
+			if !game_step(state, room, handle, dir) {
+				room.actors.remove(handle); // Hit wall or actor
+			}
+		}
+	}
+
+	let player_at = room.actors[state.player].1;
+	for handle in room.actors.handles() {
+		if let Some(&(Actor::Monster(dir, sprite), at)) = room.actors.get(handle) {
+			let dir = match game_sight(room, at, player_at) {
+				Some(dir) if !state.dead => dir, // Chase
+				_ => game_wander(room, at, dir, &mut state.rng)
+			};
+			room.actors[handle].0 = Actor::Monster(dir, sprite);
+			game_step(state, room, handle, dir);
+		}
 	}
 }
 
-pub fn game_move(state:&mut GameState, room:&mut Room, dir:Dir) {
-    let (player@Actor::Player(mut player_dir), mut player_at) = room.actors[state.player_idx]
+// Returns false if no turn was taken
+pub fn game_move(state:&mut GameState, room:&mut Room, dir:Dir) -> bool {
+    if state.dead { return false }
+    let (Actor::Player(player_dir), _) = room.actors[state.player]
     	else { panic!("Player not found where expected"); };
+
     if dir == player_dir {
-        if 0 != room.routes[ivec_to_index(player_at)] & (1 << player_dir as u8) {
-            let want_at = player_at + DIR_COMPASS[dir as usize];
-            let mut deletes = Vec::<usize>::default();
-
-            'collide: {
-            	for (idx, (actor, at)) in room.actors.iter().enumerate() {
-	                if want_at == *at {
-	                	let (halt, destroy) = game_collide(state, &player, &actor, want_at);
-	                	if destroy { deletes.push(idx); }
-	                	if halt { break 'collide; }
-	                }
-            	}
-
-            	player_at = want_at;
-            }
-
-            for &target in deletes.iter().rev() { // FIXME: use slots or something
-            	room.actors.remove(target);
-            	if state.player_idx > target { state.player_idx -= 1 } // FIXME: USE SLOTS!!
-            }
+        game_step(state, room, state.player, dir);
+        if state.exiting { // Through the door; nothing else in this room matters
+            state.exiting = false;
+            state.level += 1;
+            state.score += DOOR_SCORE;
+            *room = game_room(state);
+            return true
         }
     } else {
-        player_dir = dir;
+        room.actors[state.player].0 = Actor::Player(dir);
     }
-    room.actors[state.player_idx] = (Actor::Player(player_dir), player_at);
+
+    game_turn(state, room);
+    room_look(room);
+    true
+}
+
+// Spawn a shot in the direction the player faces, then take a turn. With no ammo, nothing happens
+// Returns false if no turn was taken
+pub fn game_fire(state:&mut GameState, room:&mut Room) -> bool {
+    let (Actor::Player(player_dir), player_at) = room.actors[state.player]
+    	else { panic!("Player not found where expected"); };
+    if state.dead || state.ammo == 0 { return false }
+
+    state.ammo -= 1;
+    room.actors.insert((Actor::Shot(player_dir), player_at));
+
+    game_turn(state, room);
+    true
+}
+
+// Let the world take a turn without the player doing anything, as when realtime play ticks by
+// Returns false if no turn was taken
+pub fn game_wait(state:&mut GameState, room:&mut Room) -> bool {
+    if state.dead { return false }
+    game_turn(state, room);
+    room_look(room);
+    true
 }
@@ -0,0 +1,55 @@
+// Undo and redo. Turns are few and rooms are small, so just snapshot everything each turn
+
+use std::collections::VecDeque;
+
+use crate::game::*;
+use crate::room::*;
+
+pub struct History {
+	depth: usize, // 0 disables undo
+	undo: VecDeque<(GameState, Room)>,
+	redo: Vec<(GameState, Room)>,
+}
+
+impl History {
+	pub fn new(depth:usize) -> Self {
+		Self { depth, undo: VecDeque::default(), redo: Vec::default() }
+	}
+
+	// Run one turn (f returns whether a turn was taken) and remember how things were before it
+	pub fn act(&mut self, state:&mut GameState, room:&mut Room, f:impl FnOnce(&mut GameState, &mut Room) -> bool) -> bool {
+		if self.depth == 0 { return f(state, room) } // Nothing to remember, so don't copy the room either
+		let before = (state.clone(), room.clone());
+		let acted = f(state, room);
+		if acted {
+			if self.undo.len() >= self.depth { self.undo.pop_front(); }
+			self.undo.push_back(before);
+			self.redo.clear();
+		}
+		acted
+	}
+
+	// Returns false if there was nothing to undo
+	pub fn undo(&mut self, state:&mut GameState, room:&mut Room) -> bool {
+		let Some((undo_state, undo_room)) = self.undo.pop_back() else { return false };
+		self.redo.push((std::mem::replace(state, undo_state), std::mem::replace(room, undo_room)));
+		true
+	}
+
+	// Returns false if there was nothing to redo
+	pub fn redo(&mut self, state:&mut GameState, room:&mut Room) -> bool {
+		let Some((redo_state, redo_room)) = self.redo.pop() else { return false };
+		self.undo.push_back((std::mem::replace(state, redo_state), std::mem::replace(room, redo_room)));
+		true
+	}
+
+	// Something changed that isn't an undo step (realtime ticks), so what was undone can't come back
+	pub fn branch(&mut self) {
+		self.redo.clear();
+	}
+
+	pub fn clear(&mut self) {
+		self.undo.clear();
+		self.redo.clear();
+	}
+}
@@ -0,0 +1,205 @@
+// Hand-authored levels as plain text. Cells sit at odd rows and columns; the characters
+// between them are walls (any non-space) or open passages (space). Corners are ignored.
+//
+//   +-+-+-+-+
+//   |@    |D|
+//   + +-+ + +
+//   |k M    |
+//   +-+-+-+-+
+//
+// Cell glyphs: @ or > < v ^ player (facing right, or the arrow's way), D door,
+// k/K key (K is the rightward one; keys are only picked up moving the way they face, so need
+// a way in from behind), r/R g/G b/B red, green and blue keys, X Y Z red, green and blue gates,
+// a ammo, M monster (or 0-7 for a particular monster sprite).
+// Space or . is an empty cell.
+//
+// level_write produces the same format. Shots, monster facing, and all but one actor
+// per cell (the player wins, then whichever is first) are not represented.
+
+use std::collections::VecDeque;
+
+use crate::constants::*;
+use crate::room::*;
+
+use glam::IVec2;
+use ndarray::Array2;
+
+const KEY_GLYPH:[char; KEY_KINDS as usize] = ['k', 'r', 'g', 'b']; // Leftward; uppercase is rightward
+const GATE_GLYPH:[char; KEY_KINDS as usize - 1] = ['X', 'Y', 'Z']; // From kind 1
+
+// Text column/row (1-based) of a cell, for error messages
+fn level_place(at:IVec2) -> String {
+	format!("line {}, column {}", at.y*2+2, at.x*2+2)
+}
+
+fn level_actor(glyph:char, monsters:&mut u32) -> Result<Option<Actor>, ()> {
+	Ok(Some(match glyph {
+		'@' | '>' => Actor::Player(Dir::Right),
+		'<' => Actor::Player(Dir::Left),
+		'v' => Actor::Player(Dir::Down),
+		'^' => Actor::Player(Dir::Up),
+		'D' => Actor::Door,
+		_ if KEY_GLYPH.contains(&glyph.to_ascii_lowercase()) =>
+			Actor::Key(glyph.is_ascii_uppercase(), KEY_GLYPH.iter().position(|&key| key == glyph.to_ascii_lowercase()).unwrap() as u8),
+		_ if GATE_GLYPH.contains(&glyph) => Actor::Gate(1 + GATE_GLYPH.iter().position(|&gate| gate == glyph).unwrap() as u8),
+		'a' => Actor::Ammo,
+		'M' => {
+			*monsters += 1;
+			Actor::Monster(Dir::Right, ((*monsters - 1) % MONSTER_COUNT) as u8)
+		},
+		'0'..='9' if glyph.to_digit(10).unwrap() < MONSTER_COUNT => Actor::Monster(Dir::Right, glyph.to_digit(10).unwrap() as u8),
+		' ' | '.' => return Ok(None),
+		_ => return Err(())
+	}))
+}
+
+fn level_glyph(actor:&Actor) -> Option<char> {
+	Some(match actor {
+		Actor::Player(Dir::Right) => '>',
+		Actor::Player(Dir::Left) => '<',
+		Actor::Player(Dir::Down) => 'v',
+		Actor::Player(Dir::Up) => '^',
+		Actor::Door => 'D',
+		Actor::Key(rightward, kind) => {
+			let glyph = *KEY_GLYPH.get(*kind as usize)?;
+			if *rightward { glyph.to_ascii_uppercase() } else { glyph }
+		},
+		Actor::Gate(kind) => *GATE_GLYPH.get((*kind as usize).checked_sub(1)?)?,
+		Actor::Ammo => 'a',
+		Actor::Monster(_, sprite) => char::from_digit(*sprite as u32, 10)?,
+		Actor::Shot(_) => return None
+	})
+}
+
+pub fn level_write(room:&Room) -> String {
+	let (height, width) = room.routes.dim();
+	let mut grid = vec![vec![' '; width*2+1]; height*2+1];
+
+	for row in grid.iter_mut().step_by(2) {
+		for corner in row.iter_mut().step_by(2) { *corner = '+'; }
+	}
+	for ((y, x), &route) in room.routes.indexed_iter() {
+		let (tx, ty) = (x*2+1, y*2+1);
+		// Each cell draws its own right and bottom sides; the first row and column draw the outside
+		if 0 == route & DirMask::Right as u8 { grid[ty][tx+1] = '|'; }
+		if 0 == route & DirMask::Down as u8 { grid[ty+1][tx] = '-'; }
+		if x == 0 && 0 == route & DirMask::Left as u8 { grid[ty][tx-1] = '|'; }
+		if y == 0 && 0 == route & DirMask::Up as u8 { grid[ty-1][tx] = '-'; }
+	}
+
+	for (_, (actor, at)) in room.actors.iter() {
+		let Some(glyph) = level_glyph(actor) else { continue };
+		let cell = &mut grid[at.y as usize*2+1][at.x as usize*2+1];
+		if *cell == ' ' || matches!(actor, Actor::Player(_)) { *cell = glyph; }
+	}
+
+	grid.into_iter().map(|row| row.into_iter().collect::<String>() + "\n").collect()
+}
+
+pub fn level_read(text:&str) -> Result<Room, String> {
+	let lines:Vec<Vec<char>> = text.lines().map(|line| line.trim_end().chars().collect()).collect();
+	let text_height = lines.len();
+	let text_width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+	if text_height < 3 || text_width < 3 { return Err("Level is smaller than one cell".to_string()) }
+	if text_height % 2 == 0 { return Err(format!("Level has {} lines, but needs an odd number (walls above and below every row)", text_height)) }
+	let text_width = text_width | 1; // Last column may be trimmed blank, or short a border
+
+	let size = IVec2::new((text_width/2) as i32, (text_height/2) as i32);
+	let char_at = |x:usize, y:usize| lines[y].get(x).copied().unwrap_or(' ');
+	let open = |x:usize, y:usize| char_at(x, y) == ' ';
+
+	let mut routes:Array2<u8> = Array2::default(ivec_to_index(size));
+	let mut actors:Vec<(Actor, IVec2)> = Vec::default();
+	let mut monsters = 0;
+	for y in 0..size.y {
+		for x in 0..size.x {
+			let at = IVec2::new(x, y);
+			let (tx, ty) = (x as usize*2+1, y as usize*2+1);
+			let glyph = char_at(tx, ty);
+			match level_actor(glyph, &mut monsters) {
+				Ok(Some(actor)) => actors.push((actor, at)),
+				Ok(None) => (),
+				Err(()) => return Err(format!("Unknown glyph '{}' at {}", glyph, level_place(at)))
+			}
+
+			for (dir, offset) in DIR_ALL.iter().zip(DIR_COMPASS) {
+				let (lx, ly) = ((tx as i32 + offset.x) as usize, (ty as i32 + offset.y) as usize);
+				if open(lx, ly) {
+					let neighbor = at + offset;
+					if !(IVec2::ZERO.cmple(neighbor).all() && size.cmpgt(neighbor).all()) {
+						return Err(format!("Passage leads out of the level at line {}, column {}", ly+1, lx+1))
+					}
+					routes[ivec_to_index(at)] |= 1 << *dir as u8;
+				}
+			}
+		}
+	}
+
+	let player_at = room_check("Level", &routes, &actors)?;
+	if !actors.iter().any(|(actor, _)| *actor == Actor::Door) { return Err("Level has no door (D)".to_string()) }
+
+	// Flood from the player; everything placed must be reachable
+	let mut reached:Array2<bool> = Array2::default(ivec_to_index(size));
+	let mut queue = VecDeque::from([player_at]);
+	reached[ivec_to_index(player_at)] = true;
+	while let Some(at) = queue.pop_front() {
+		for (dir, offset) in DIR_ALL.iter().zip(DIR_COMPASS) {
+			let next = at + offset;
+			if 0 != routes[ivec_to_index(at)] & (1 << *dir as u8) && !reached[ivec_to_index(next)] {
+				reached[ivec_to_index(next)] = true;
+				queue.push_back(next);
+			}
+		}
+	}
+	for (actor, at) in &actors {
+		if !reached[ivec_to_index(*at)] {
+			return Err(format!("{:?} at {} can't be reached from the player", actor, level_place(*at)))
+		}
+		if let Actor::Key(rightward, _) = actor {
+			let approach = (room_key_dir(*rightward) as usize + 2) % 4;
+			if 0 == routes[ivec_to_index(*at)] & 1<<approach {
+				return Err(format!("Key at {} faces {:?}, but there's no way in from behind it", level_place(*at), room_key_dir(*rightward)))
+			}
+		}
+	}
+
+	let walls = room_walls(&routes);
+	let seen = Array2::default(routes.dim());
+	let mut room = Room { routes, walls, actors:actors.into_iter().collect(), seen };
+	room_look(&mut room);
+	Ok(room)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::*;
+	use crate::maze::*;
+
+	#[test]
+	fn level_round_trip() {
+		for (seed, kind) in (0..24).zip(MAZE_KINDS.iter().cycle()) {
+			let mut state = GameState::new(seed);
+			state.level = seed as u32 % 8;
+			state.maze = MazeStyle { kind:kind.0, braid:(seed as u32 % 3)*30, ..MazeStyle::default() };
+			let room = game_room(&mut state);
+
+			let text = level_write(&room);
+			let read = level_read(&text).unwrap_or_else(|err| panic!("seed {}: {}\n{}", seed, err, text));
+			assert_eq!(read.routes, room.routes);
+			assert_eq!(read.walls, room.walls);
+			assert_eq!(level_write(&read), text);
+
+			// Everything in the same place, except that which way monsters face isn't written
+			let actors = |room:&Room| {
+				let mut actors:Vec<(Actor, IVec2)> = room.actors.iter().map(|(_, &(actor, at))| match actor {
+					Actor::Monster(_, sprite) => (Actor::Monster(Dir::Right, sprite), at),
+					_ => (actor, at)
+				}).collect();
+				actors.sort_by_key(|&(_, at)| (at.y, at.x));
+				actors
+			};
+			assert_eq!(actors(&read), actors(&room));
+		}
+	}
+}
@@ -0,0 +1,33 @@
+// The game as a library. Without the "graphics" feature, only the game simulation is built, for
+// headless use; the desktop game itself is the binary in main.rs
+
+pub mod camera;
+pub mod constants;
+pub mod game;
+pub mod history;
+pub mod level;
+pub mod maze;
+pub mod palette;
+pub mod raster;
+pub mod render;
+pub mod replay;
+pub mod room;
+pub mod save;
+pub mod session;
+pub mod slots;
+pub mod solve;
+pub mod texture;
+pub mod tween;
+#[cfg(feature = "graphics")]
+pub mod blit;
+#[cfg(feature = "graphics")]
+pub mod quad;
+#[cfg(all(feature = "graphics", not(target_arch = "wasm32")))]
+pub mod offscreen;
+
+// On the web the library is what gets loaded, so it carries the binary's entry point too
+#[cfg(all(feature = "graphics", target_arch = "wasm32"))]
+extern crate self as wgpu_hello;
+#[cfg(all(feature = "graphics", target_arch = "wasm32"))]
+#[path = "main.rs"]
+mod web_main;
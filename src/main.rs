@@ -1,33 +1,195 @@
-// Entry point
+// Entry point: the game in a window, or a screenshot without one. Needs the "graphics" feature
 
-mod constants;
-mod game;
-mod quad;
-mod room;
-mod texture;
-
-use std::borrow::Cow;
+use std::collections::VecDeque;
 use winit::{
     event::{Event, DeviceEvent, WindowEvent, ElementState, KeyboardInput, VirtualKeyCode},
     event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
-use glam::{IVec2};
+use glam::{IVec2, Vec2};
 
+#[cfg(not(target_arch = "wasm32"))]
+use wgpu_hello::offscreen::*;
 #[cfg(target_arch="wasm32")]
 use wasm_bindgen::prelude::*;
 #[cfg(target_arch="wasm32")]
 use winit::platform::web::WindowExtWebSys;
 
-use crate::constants::*;
-use crate::game::*;
-use crate::quad::*;
-use crate::room::*;
-use crate::texture::*;
+use wgpu_hello::{blit::*, camera::*, constants::*, game::*, history::*, level::*, maze::*, palette::*, quad::*, raster::*, render::*, replay::*, room::*, save::*, session::*, solve::*, texture::*, tween::*};
+
+// Option from "--name value" on desktop or "?name=value" on web
+fn launch_arg(name:&str) -> Option<String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let args:Vec<String> = std::env::args().collect();
+        let flag = format!("--{}", name);
+        args.iter().position(|arg| *arg == flag)
+            .and_then(|idx| args.get(idx+1))
+            .cloned()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let search = web_sys::window()?.location().search().ok()?;
+        let prefix = format!("{}=", name);
+        search.trim_start_matches('?').split('&')
+            .find_map(|pair| pair.strip_prefix(prefix.as_str()))
+            .map(String::from)
+    }
+}
+
+// Bad launch option: say what's wrong and quit, so typos aren't silently ignored. On web there's
+// no quitting, so panic and it shows in the console
+fn launch_fail(message:&str) -> ! {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        eprintln!("{}", message);
+        std::process::exit(2)
+    }
+    #[cfg(target_arch = "wasm32")]
+    panic!("{}", message)
+}
+
+// Numeric launch option
+fn launch_arg_parse<T:std::str::FromStr>(name:&str) -> Option<T> {
+    launch_arg(name).map(|value| value.parse().unwrap_or_else(|_| launch_fail(&format!("{} must be a number", name))))
+}
+
+// Numeric launch option that also has to pass a check; bad says when it doesn't, must says what it should be
+fn launch_arg_check<T:std::str::FromStr>(name:&str, must:&str, bad:impl Fn(&T) -> bool) -> Option<T> {
+    launch_arg_parse(name).map(|value| if bad(&value) { launch_fail(&format!("{} must be {}", name, must)) } else { value })
+}
+
+// Only show what the player has seen, unless --fog off
+fn launch_fog() -> bool {
+    match launch_arg("fog").as_deref() {
+        None | Some("on") => true,
+        Some("off") => false,
+        Some(other) => launch_fail(&format!("Unknown fog {}; try on or off", other))
+    }
+}
+
+// Rooms bigger than the canvas scroll with the player, unless --view fit asks to shrink them instead
+fn launch_camera(room:&Room) -> Option<Camera> {
+    match launch_arg("view").as_deref() {
+        None | Some("scroll") => Some(camera_make(room,
+            Vec2::splat(launch_arg_parse("dead-zone").unwrap_or(CAMERA_DEAD_ZONE)),
+            launch_arg_parse("ease").unwrap_or(CAMERA_EASE))),
+        Some("fit") => None,
+        Some(other) => launch_fail(&format!("Unknown view {}; try scroll or fit", other))
+    }
+}
+
+// Index into PALETTES
+fn launch_palette() -> usize {
+    launch_arg("palette").map(|name| palette_parse(&name).unwrap_or_else(|| {
+        let names:Vec<&str> = PALETTES.iter().map(|palette| palette.name).collect();
+        launch_fail(&format!("Unknown palette {}; try {}", name, names.join(", ")))
+    })).unwrap_or(0)
+}
+
+// Threshold unless asked
+fn launch_shading() -> Shading {
+    launch_arg("shading").map(|name| shading_parse(&name).unwrap_or_else(|| {
+        let names:Vec<&str> = SHADINGS.iter().map(|(_, name)| *name).collect();
+        launch_fail(&format!("Unknown shading {}; try {}", name, names.join(", ")))
+    })).unwrap_or(Shading::Threshold)
+}
+
+// Start from the --replay file or --level file if given (desktop), otherwise resume the saved game,
+// otherwise a new game. With --record, every input is written to that file as it happens
+// (a replay can't describe a hand-made level, so --level doesn't record)
+fn session_launch() -> Session {
+    let seed = launch_arg_parse("seed");
+    let history_depth = launch_arg_parse("history").unwrap_or(HISTORY_DEPTH);
+    let record = cfg!(not(target_arch = "wasm32")) && launch_arg("record").is_some();
+    let maze_kind = launch_arg("maze").map(|name| maze_kind_parse(&name).unwrap_or_else(|| {
+        let names:Vec<&str> = MAZE_KINDS.iter().map(|(_, name)| *name).collect();
+        launch_fail(&format!("Unknown maze {}; try {}", name, names.join(", ")))
+    }));
+    let maze_braid = launch_arg_parse("braid");
+    let maze_size = launch_arg("size").map(|size| {
+        let side = |side:&str| side.parse::<i32>().ok().filter(|&side| side >= 2);
+        size.split_once('x').and_then(|(width, height)| Some(IVec2::new(side(width)?, side(height)?)))
+            .unwrap_or_else(|| launch_fail("size must look like 16x9, at least 2x2"))
+    });
+    let maze_difficulty = launch_arg("difficulty").map(|band| maze_difficulty_parse(&band)
+        .unwrap_or_else(|| launch_fail("difficulty must look like 20-40")));
+    let maze_default = MazeStyle::default();
+    let maze = MazeStyle {
+        kind:maze_kind.unwrap_or(maze_default.kind),
+        braid:maze_braid.unwrap_or(maze_default.braid),
+        size:maze_size.unwrap_or(maze_default.size),
+        difficulty:maze_difficulty.or(maze_default.difficulty)
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = launch_arg("replay") {
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("Couldn't read {}: {}", path, err));
+        let replay = replay_read(&text).unwrap_or_else(|err| panic!("{}: {}", path, err));
+        let mut session = replay_play(&replay);
+        if record { session.record = Some(replay) } // Carry on from where the recording left off
+        return session
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = launch_arg("level") {
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|err| panic!("Couldn't read {}: {}", path, err));
+        let room = level_read(&text).unwrap_or_else(|err| panic!("{}: {}", path, err));
+        let mut session = session_make(seed.unwrap_or_else(rand::random), maze, history_depth, false);
+        session.state.player = room_player(&room).unwrap(); // level_read guarantees one
+        session.room = room;
+        return session
+    }
 
-const FORCE_MULTIPLE: Option<i32> = Some(128);
+    // A seed, maze choice or recording means the player wants a fresh game
+    if seed.is_none() && maze_kind.is_none() && maze_braid.is_none() && maze_size.is_none() && maze_difficulty.is_none() && !record {
+        if let Some(text) = save_fetch() {
+            match save_read(&text) {
+                Ok((state, room)) => return Session { state, room, history:History::new(history_depth), record:None },
+                Err(err) => log::warn!("Ignoring saved game: {}", err)
+            }
+        }
+    }
+
+    session_make(seed.unwrap_or_else(rand::random), maze, history_depth, record)
+}
+
+// Dump the room for bug reports: level text and PNG on desktop, level text to the console on web
+fn room_export(room:&Room, atlas:&image::GrayImage, palette:&Palette, shading:Shading) {
+    let text = level_write(room);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        const LEVEL_FILE:&str = "zap_room.txt";
+        const IMAGE_FILE:&str = "zap_room.png";
+        let result = std::fs::write(LEVEL_FILE, &text).map_err(|err| err.to_string())
+            .and_then(|_| raster_room(room, atlas, palette, shading).save(IMAGE_FILE).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => log::info!("Wrote {} and {}", LEVEL_FILE, IMAGE_FILE),
+            Err(err) => log::error!("Couldn't export room: {}", err)
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (atlas, palette, shading);
+        log::info!("Room:\n{}", text);
+    }
+}
 
-const CLEAR_COLOR: wgpu::Color = wgpu::Color { r:250./255., g:236./255., b:209./255., a:1. };
+// Draw the starting room to a PNG without opening a window, then quit. Takes the same options
+// as the game, so --seed or --level pick the room
+#[cfg(not(target_arch = "wasm32"))]
+async fn screenshot(path:&str) {
+    let session = session_launch();
+    let atlas = load_sprite_atlas(&mut game_rng(session.state.seed, ATLAS_RNG_STREAM)).await;
+    let view = match launch_camera(&session.room) {
+        Some(camera) => (camera_origin(&camera), 1.),
+        None => room_fit(&session.room)
+    };
+    let image = offscreen_render(&session.room, atlas, &PALETTES[launch_palette()], launch_shading(), view, launch_fog(), false).await
+        .unwrap_or_else(|err| panic!("Couldn't render: {}", err));
+    image.save(path).unwrap_or_else(|err| panic!("Couldn't write {}: {}", path, err));
+    log::info!("Wrote {}", path);
+}
 
 // Silently fails if texture is bigger than 2^31 on either axis. Whatever
 fn extent_xy_to_ivec(v:wgpu::Extent3d) -> IVec2 {
@@ -89,99 +251,82 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         .await
         .expect("Failed to create device");
 
+    let mut session = session_launch();
+    log::info!("Seed {}", session.state.seed);
+    #[cfg(not(target_arch = "wasm32"))]
+    let record_path = launch_arg("record");
+    #[cfg(not(target_arch = "wasm32"))]
+    if let (Some(path), Some(record)) = (&record_path, &session.record) {
+        std::fs::write(path, replay_write(record)).unwrap_or_else(|err| log::error!("Couldn't write {}: {}", path, err));
+    }
+
     // Build scene
-    let (sprite_atlas, sprite_atlas_view) = make_texture(&device, &queue, load_sprite_atlas().await, "sprite");
+    let atlas = load_sprite_atlas(&mut game_rng(session.state.seed, ATLAS_RNG_STREAM)).await;
+    let (sprite_atlas, sprite_atlas_view) = make_texture(&device, &queue, atlas.clone(), "sprite"); // Keep CPU copy for room_export
 
     let (root_vertex_buffer, root_index_buffer, root_vertex_layout) = make_quad_root_buffer(&device);
 
-    let (instance_buffer, instance_layout) = make_quad_instance_buffer(&device, "0"); // Returns mapped
-
-    // Write scene
-    let mut instance_buffer_count;
-    let mut room;
-    let mut state = GameState::default();
-    fn reset_game() -> (Room, usize) {
-        let room = room_make(true);
-
-        // Find player
-        let mut player_idx = None::<usize>;
-        for (idx, (actor, _)) in room.actors.iter().enumerate() {
-            if let Actor::Player(_) = actor {
-                player_idx = Some(idx);
-            }
+    let (mut instance_buffer, instance_layout) = make_quad_instance_buffer(&device, "0", room_sprite_count(&session.room)); // Returns mapped
+
+    let fog = launch_fog();
+    let mut camera = launch_camera(&session.room);
+    let mut camera_room = (session.state.seed, session.state.level); // When this changes, it's a new room; cut, don't scroll
+    // Actors slide between cells over this many seconds; --tween 0 makes moves instant
+    let tween_duration = launch_arg_parse("tween").unwrap_or(TWEEN_DURATION);
+    let mut tween:Option<Tween> = None;
+    let mut inputs:VecDeque<Input> = VecDeque::default(); // Waiting for the slide before them to finish
+    let mut animating = false; // Camera or actors still moving, so keep drawing frames
+    // Turn mode (the default) waits for the player. In realtime mode the world takes a turn every
+    // --tick seconds the player doesn't, run on a fixed timestep however fast frames come
+    let realtime = match launch_arg("mode").as_deref() {
+        None | Some("turn") => false,
+        Some("realtime") => true,
+        Some(other) => launch_fail(&format!("Unknown mode {}; try turn or realtime", other))
+    };
+    let tick_interval = launch_arg_check("tick", "more than 0 seconds", |&tick:&f64| tick.is_nan() || tick <= 0.).unwrap_or(TICK_INTERVAL);
+    let mut tick_clock = 0.; // Seconds toward the next tick
+    let mut last_frame = tween_now();
+    let mut paused = false;
+
+    fn update_instance_buffer(room: &Room, frame:&RoomFrame, device:&wgpu::Device, queue:&wgpu::Queue, instance_buffer:&mut wgpu::Buffer, sprite_atlas:&wgpu::Texture) -> u64 {
+        let sprites = room_sprite_count(room);
+        if SPRITE_SIZE*sprites > instance_buffer.size() { // Bigger room than before; layout is the same
+            *instance_buffer = make_quad_instance_buffer(device, "0", sprites).0;
         }
-        let player_idx = player_idx.unwrap();
-
-        (room, player_idx)
-    }
-
-    fn update_instance_buffer(room: &Room, queue:&wgpu::Queue, instance_buffer:&wgpu::Buffer, sprite_atlas:&wgpu::Texture) -> u64 {
-        return room_render(
+        room_render(
             room,
             queue,
             instance_buffer,
             IVec2::new(CANVAS_SIDE as i32, CANVAS_SIDE as i32),
             extent_xy_to_ivec(sprite_atlas.size()),
-            true
-       );
+            frame
+       )
     }
-    (room, state.player_idx) = reset_game();
-    instance_buffer_count = update_instance_buffer(&room, &queue, &instance_buffer, &sprite_atlas);
 
-    // Load the shaders from disk
-    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: None,
-        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-    });
-
-    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("single bind group layout"),
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type: wgpu::TextureSampleType::Float { filterable: false }, /* FIXME: Is nearest a filter? */
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    multisampled: false,
-                },
-                count: None,
-            },
-        ],
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("single pipeline"),
-        bind_group_layouts: &[&bind_group_layout],
-        push_constant_ranges: &[],
-    });
+    // Colors; C cycles through the built-in palettes
+    let mut palette_idx = launch_palette();
+    let palette_buffer = make_palette_buffer(&device, &queue, &PALETTES[palette_idx]);
 
     let swapchain_capabilities = surface.get_capabilities(&adapter);
     let swapchain_format = swapchain_capabilities.formats[0];
 
-    let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_quad",
-            buffers: &[root_vertex_layout, instance_layout],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_quad_direct",
-            targets: &[Some(swapchain_format.into())],
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            front_face: wgpu::FrontFace::Cw,
-            cull_mode: Some(wgpu::Face::Back),
-            ..wgpu::PrimitiveState::default()
-        },
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
-    });
+    // One pipeline for each shading, so B can switch between them
+    let mut shading = launch_shading();
+    let (render_pipelines, bind_group_layout) = make_quad_pipelines(&device, swapchain_format, root_vertex_layout.clone(), instance_layout);
+
+    // Scene goes to the canvas at its own resolution, then scaled up onto the window.
+    // S cycles scaling, X toggles the CRT look
+    let (_canvas, canvas_view) = make_canvas_texture(&device, swapchain_format);
+    let (blit_pipeline, blit_bind_group, blit_uniform_buffer) = make_blit_pipeline(&device, swapchain_format, &canvas_view, root_vertex_layout.clone());
+    let mut scaling = launch_arg("scale").map(|name| scaling_parse(&name).unwrap_or_else(|| {
+        let names:Vec<&str> = SCALINGS.iter().map(|(_, name)| *name).collect();
+        launch_fail(&format!("Unknown scale {}; try {}", name, names.join(", ")))
+    })).unwrap_or(Scaling::Integer);
+    let mut crt = match launch_arg("crt").as_deref() {
+        None | Some("off") => false,
+        Some("on") => true,
+        Some(other) => launch_fail(&format!("Unknown crt {}; try on or off", other))
+    };
 
     let mut config = wgpu::SurfaceConfiguration {
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -199,7 +344,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         // Have the closure take ownership of the resources.
         // `event_loop.run` never returns, therefore we must do this to ensure
         // the resources are properly cleaned up.
-        let _ = (&instance, &adapter, &shader, &pipeline_layout);
+        let _ = (&instance, &adapter);
 
         *control_flow = ControlFlow::Wait;
         match event {
@@ -215,6 +360,65 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 window.request_redraw();
             }
             Event::RedrawRequested(_) => {
+                let now = tween_now();
+                if realtime && !paused {
+                    tick_clock += now - last_frame;
+                    while tick_clock >= tick_interval {
+                        tick_clock -= tick_interval;
+                        if inputs.is_empty() { inputs.push_back(Input::Wait); } // Anything already waiting takes this turn instead
+                    }
+                }
+                last_frame = now;
+
+                // Once actors have finished sliding, take the next waiting input
+                if !tween.as_mut().map_or(false, |tween| tween_step(tween, now)) && !paused {
+                    tween = None;
+                    while let Some(input) = inputs.pop_front() {
+                        let input = match input {
+                            Input::Move(_) | Input::Fire if session.state.dead => Input::Reset(rand::random()), // Any move after death starts over
+                            _ => input
+                        };
+                        let before = tween_make(&session.room, tween_duration);
+                        if !session_input(&mut session, input) { continue } // Nothing happened, try the next
+                        if matches!(input, Input::Move(_) | Input::Fire) { tick_clock = 0.; } // Player acted, so the world's turn is taken
+
+                        if let Input::Reset(seed) = input { log::info!("Seed {}", seed); }
+
+                        // Idle ticks don't rewrite the save every half second; the next real input, or closing, catches it up
+                        if input != Input::Wait {
+                            if let Err(err) = save_store(&save_write(&session.state, &session.room)) { log::error!("{}", err); }
+                        }
+
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let (Some(path), Some(record)) = (&record_path, &session.record) {
+                            use std::io::Write;
+                            std::fs::OpenOptions::new().append(true).open(path)
+                                .and_then(|mut file| file.write_all(replay_write_last(record).as_bytes()))
+                                .unwrap_or_else(|err| log::error!("Couldn't write {}: {}", path, err));
+                        }
+
+                        if camera_room != (session.state.seed, session.state.level) {
+                            camera_room = (session.state.seed, session.state.level);
+                            log::info!("Level {}: {:?}", session.state.level, solve_metrics(&session.room));
+                            if let Some(camera) = camera.as_mut() { *camera = camera_make(&session.room, camera.dead_zone, camera.ease); }
+                        } else if tween_duration > 0. {
+                            tween = Some(before); // Same room, so the actors can slide
+                        }
+                        break
+                    }
+                }
+
+                // Ease the camera along, one step per frame until it settles
+                let camera_moved = camera.as_mut().map_or(false, |camera| camera_step(camera, &session.room));
+                animating = camera_moved || tween.is_some() || !inputs.is_empty() || (realtime && !paused);
+                let room_frame = RoomFrame {
+                    view: match camera.as_ref() { Some(camera) => (camera_origin(camera), 1.), None => room_fit(&session.room) },
+                    fog,
+                    tween: tween.as_ref(),
+                    actor_draw: true,
+                };
+                let instance_buffer_count = update_instance_buffer(&session.room, &room_frame, &device, &queue, &mut instance_buffer, &sprite_atlas);
+
                 let frame = surface
                     .get_current_texture()
                     .expect("Failed to acquire next swap chain texture");
@@ -223,55 +427,46 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     .create_view(&wgpu::TextureViewDescriptor::default());
                 let mut encoder =
                     device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                let paper = {
+                    let [r, g, b] = palette_color(PALETTES[palette_idx].paper).map(|channel| channel as f64);
+                    wgpu::Color { r, g, b, a:1. }
+                };
                 {
-                    // Get viewport within window
-                    // FIXME: Better to let IVec handle this?
-                    let (offset, size) =  {
-                        let (offset, size) =  {
-                            let size = IVec2::new(config.width.try_into().unwrap(),
-                                                  config.height.try_into().unwrap());
-                            let diff = size.y - size.x;
-                            if diff == 0 {
-                                (IVec2::ZERO, size)
-                            } else if diff < 0 {
-                                (IVec2::new(-diff/2, 0), IVec2::new(size.y, size.y))
-                            } else {
-                                (IVec2::new(0, diff/2), IVec2::new(size.x, size.x))
-                            }
-                        };
+                    let bind_group = make_quad_bind_group(&device, &bind_group_layout, &sprite_atlas_view, &palette_buffer);
 
-                        if let Some(radix) = FORCE_MULTIPLE { // Assumes made square in previous step
-                            if size.x > radix {
-                                let reduce_by = size.x % radix;
-                                let reduced_side = size.x - reduce_by;
-
-                                (offset + reduce_by/2, IVec2::new(reduced_side, reduced_side))
-                            } else {
-                                (offset, size)
-                            }
-                        } else {
-                            (offset, size)
-                        }
-                    };
-
-                    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        entries: &[
-                            wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: wgpu::BindingResource::TextureView(&sprite_atlas_view),
-                            }
-                        ],
-                        layout: &bind_group_layout,
-                        label: Some("frame bind group"),
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &canvas_view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(paper),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
                     });
 
+                    rpass.set_pipeline(&render_pipelines[shading as usize]);
+                    rpass.set_vertex_buffer(0, root_vertex_buffer.slice(..));
+                    rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    rpass.set_index_buffer(root_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                    rpass.set_bind_group(0, &bind_group, &[]);
+                    rpass.draw_indexed(0..6, 0, 0..(instance_buffer_count as u32));
+                }
+                {
+                    // Canvas onto the window, centered; the border is paper too
+                    let window_size = IVec2::new(config.width as i32, config.height as i32);
+                    let (offset, size) = blit_rect(window_size, scaling);
+                    queue.write_buffer(&blit_uniform_buffer, 0, bytemuck::cast_slice(&blit_uniform(size, scaling, crt)));
+
                     let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: None,
                         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                             view: &view,
                             resolve_target: None,
                             ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(CLEAR_COLOR),
+                                load: wgpu::LoadOp::Clear(paper),
                                 store: true,
                             },
                         })],
@@ -279,40 +474,86 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     });
 
                     rpass.set_viewport(offset.x as f32, offset.y as f32, size.x as f32, size.y as f32, 0., 1.);
-                    rpass.set_pipeline(&render_pipeline);
+                    rpass.set_pipeline(&blit_pipeline);
                     rpass.set_vertex_buffer(0, root_vertex_buffer.slice(..));
-                    rpass.set_vertex_buffer(1, instance_buffer.slice(..));
                     rpass.set_index_buffer(root_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                    rpass.set_bind_group(0, &bind_group, &[]);
-                    rpass.draw_indexed(0..6, 0, 0..(instance_buffer_count as u32));
+                    rpass.set_bind_group(0, &blit_bind_group, &[]);
+                    rpass.draw_indexed(0..6, 0, 0..1);
                 }
 
                 queue.submit(Some(encoder.finish()));
                 frame.present();
             }
+            // Last event of each pass through the loop, so this decides how long to sleep
+            Event::RedrawEventsCleared => if animating {
+                window.request_redraw();
+                #[cfg(not(target_arch = "wasm32"))]
+                { *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + std::time::Duration::from_secs_f64(FRAME_INTERVAL)); }
+                #[cfg(target_arch = "wasm32")]
+                { *control_flow = ControlFlow::Poll; } // The browser paces frames itself
+            },
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
-            } => *control_flow = ControlFlow::Exit,
+            } => {
+                if let Err(err) = save_store(&save_write(&session.state, &session.room)) { log::error!("{}", err); }
+                *control_flow = ControlFlow::Exit
+            },
             // Recomment when a more sensible frame approach in place, but then uncomment again for RenderDoc.
             Event::WindowEvent {
                 event: WindowEvent::KeyboardInput {input:
                     KeyboardInput{state: ElementState::Pressed, virtual_keycode:Some(key), ..}, ..},
                 ..
             } =>  {
-                if match key { // return true for redraw
-                    VirtualKeyCode::Escape | VirtualKeyCode::Delete | VirtualKeyCode::Back => {
-                        state = Default::default();
-                        (room, state.player_idx) = reset_game();
-                        true
+                if key == VirtualKeyCode::P {
+                    paused = !paused;
+                    last_frame = tween_now(); // Time spent paused doesn't count toward a tick
+                    log::info!("{}", if paused { "Paused" } else { "Unpaused" });
+                    window.request_redraw();
+                    return
+                }
+                if let Some(input) = match key {
+                    VirtualKeyCode::F12 => { room_export(&session.room, &atlas, &PALETTES[palette_idx], shading); None },
+                    VirtualKeyCode::B => {
+                        let idx = SHADINGS.iter().position(|&(other, _)| other == shading).unwrap();
+                        let (next, name) = SHADINGS[(idx + 1) % SHADINGS.len()];
+                        shading = next;
+                        log::info!("Shading {}", name);
+                        window.request_redraw();
+                        None
+                    },
+                    VirtualKeyCode::S => {
+                        let idx = SCALINGS.iter().position(|&(other, _)| other == scaling).unwrap();
+                        scaling = SCALINGS[(idx + 1) % SCALINGS.len()].0;
+                        log::info!("Scale {}", SCALINGS[(idx + 1) % SCALINGS.len()].1);
+                        window.request_redraw();
+                        None
                     },
-                    VirtualKeyCode::Right => { game_move(&mut state, &mut room, Dir::Right); true },
-                    VirtualKeyCode::Down => { game_move(&mut state, &mut room, Dir::Down); true },
-                    VirtualKeyCode::Left => { game_move(&mut state, &mut room, Dir::Left); true },
-                    VirtualKeyCode::Up => { game_move(&mut state, &mut room, Dir::Up); true },
-                    _ => false
+                    VirtualKeyCode::X => {
+                        crt = !crt;
+                        window.request_redraw();
+                        None
+                    },
+                    VirtualKeyCode::C => {
+                        palette_idx = (palette_idx + 1) % PALETTES.len();
+                        queue.write_buffer(&palette_buffer, 0, bytemuck::cast_slice(&palette_uniform(&PALETTES[palette_idx])));
+                        log::info!("Palette {}", PALETTES[palette_idx].name);
+                        window.request_redraw();
+                        None
+                    },
+                    _ if paused => None,
+                    VirtualKeyCode::Escape | VirtualKeyCode::Delete | VirtualKeyCode::Back => Some(Input::Reset(rand::random())),
+                    VirtualKeyCode::Z => Some(Input::Undo),
+                    VirtualKeyCode::Y => Some(Input::Redo),
+                    VirtualKeyCode::Right => Some(Input::Move(Dir::Right)),
+                    VirtualKeyCode::Down => Some(Input::Move(Dir::Down)),
+                    VirtualKeyCode::Left => Some(Input::Move(Dir::Left)),
+                    VirtualKeyCode::Up => Some(Input::Move(Dir::Up)),
+                    VirtualKeyCode::Space => Some(Input::Fire),
+                    _ => None
                 } {
-                    instance_buffer_count = update_instance_buffer(&room, &queue, &instance_buffer, &sprite_atlas);
+                    // Taken when the redraw gets to it, once any sliding is done
+                    if inputs.len() < INPUT_QUEUE_MAX { inputs.push_back(input); }
                     window.request_redraw()
                 }
             },
@@ -320,7 +561,6 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 event: DeviceEvent::Button {state: ElementState::Pressed, ..},
                 ..
             } => {
-                instance_buffer_count = update_instance_buffer(&room, &queue, &instance_buffer, &sprite_atlas);
                 window.request_redraw()
             },
             _ => {}
@@ -330,6 +570,13 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
 #[cfg_attr(target_arch="wasm32", wasm_bindgen(start))]
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = launch_arg("screenshot") {
+        env_logger::init();
+        pollster::block_on(screenshot(&path));
+        return
+    }
+
     let event_loop = EventLoop::new();
     let window = winit::window::WindowBuilder::new()
         .with_min_inner_size(winit::dpi::LogicalSize::new(128.0, 128.0))
@@ -0,0 +1,344 @@
+// Maze carving. Each generator fills in routes (DirMask bits, always set on both sides of a
+// passage) so that every cell connects to every other, then braiding can add loops.
+// Like room_make, the same rng state always gives the same maze.
+
+use crate::constants::*;
+use crate::room::*;
+
+use glam::IVec2;
+use ndarray::Array2;
+use rand::{seq::SliceRandom, Rng, RngCore};
+
+pub trait MazeGen {
+	// Routes come in all zero
+	fn carve(&self, routes:&mut Array2<u8>, rng:&mut dyn RngCore);
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MazeKind {
+	Backtracker, // Long winding corridors (the original)
+	Prim,        // Short branches, lots of small dead ends
+	Kruskal,     // Like Prim but less centered
+	Wilson,      // Unbiased: every possible maze equally likely
+	Division,    // Long straight walls, boxy rooms
+}
+
+// Names used in launch options, saves and replays
+pub const MAZE_KINDS:[(MazeKind, &str);5] = [
+	(MazeKind::Backtracker, "backtracker"),
+	(MazeKind::Prim, "prim"),
+	(MazeKind::Kruskal, "kruskal"),
+	(MazeKind::Wilson, "wilson"),
+	(MazeKind::Division, "division"),
+];
+
+pub fn maze_kind_name(kind:MazeKind) -> &'static str {
+	MAZE_KINDS.iter().find(|(k, _)| *k == kind).unwrap().1
+}
+
+pub fn maze_kind_parse(name:&str) -> Option<MazeKind> {
+	MAZE_KINDS.iter().find(|(_, n)| *n == name).map(|(kind, _)| *kind)
+}
+
+// Everything that decides how rooms come out, apart from the rng and the level
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MazeStyle {
+	pub kind:MazeKind,
+	pub braid:u32, // Percent of dead ends opened up into loops
+	pub size:IVec2, // In cells; at least 2 each way so there's room for door, player and key
+	pub difficulty:Option<(u32, u32)>, // Inclusive band for solve_metrics difficulty; room_make retries rooms outside it
+}
+
+impl Default for MazeStyle {
+	fn default() -> Self { Self { kind:MazeKind::Backtracker, braid:0, size:IVec2::new(TILES as i32, TILES as i32), difficulty:None } }
+}
+
+// "low-high", as in launch options
+pub fn maze_difficulty_parse(text:&str) -> Option<(u32, u32)> {
+	let (low, high) = text.split_once('-')?;
+	let (low, high) = (low.parse().ok()?, high.parse().ok()?);
+	(low <= high).then_some((low, high))
+}
+
+// As in saves and replays: "prim 30 16 9", then "20-40" if there's a difficulty band
+pub fn maze_style_write(style:&MazeStyle) -> String {
+	let mut out = format!("{} {} {} {}", maze_kind_name(style.kind), style.braid, style.size.x, style.size.y);
+	if let Some((low, high)) = style.difficulty { out.push_str(&format!(" {}-{}", low, high)); }
+	out
+}
+
+pub fn maze_style_read(text:&str) -> Option<MazeStyle> {
+	let words:Vec<&str> = text.split_whitespace().collect();
+	let side = |which:usize| words[which].parse::<i32>().ok().filter(|&side| side >= 2);
+	if !(4..=5).contains(&words.len()) { return None }
+	let size = IVec2::new(side(2)?, side(3)?);
+	let difficulty = match words.get(4) {
+		Some(band) => Some(maze_difficulty_parse(band)?),
+		None => None
+	};
+	Some(MazeStyle { kind:maze_kind_parse(words[0])?, braid:words[1].parse().ok()?, size, difficulty })
+}
+
+pub fn maze_gen(kind:MazeKind) -> &'static dyn MazeGen {
+	match kind {
+		MazeKind::Backtracker => &Backtracker,
+		MazeKind::Prim => &Prim,
+		MazeKind::Kruskal => &Kruskal,
+		MazeKind::Wilson => &Wilson,
+		MazeKind::Division => &Division,
+	}
+}
+
+pub fn maze_carve(style:&MazeStyle, routes:&mut Array2<u8>, rng:&mut dyn RngCore) {
+	maze_gen(style.kind).carve(routes, rng);
+	maze_braid(routes, style.braid, rng);
+}
+
+fn maze_bound(routes:&Array2<u8>) -> IVec2 {
+	let (height, width) = routes.dim();
+	IVec2::new(width as i32, height as i32)
+}
+
+// Passage from at toward DIR_COMPASS[compass_idx], on both sides
+fn maze_open(routes:&mut Array2<u8>, at:IVec2, compass_idx:usize) {
+	routes[ivec_to_index(at)] |= 1<<compass_idx;
+	routes[ivec_to_index(at + DIR_COMPASS[compass_idx])] |= 1<<((compass_idx+2)%4);
+}
+
+fn maze_close(routes:&mut Array2<u8>, at:IVec2, compass_idx:usize) {
+	routes[ivec_to_index(at)] &= !(1<<compass_idx);
+	routes[ivec_to_index(at + DIR_COMPASS[compass_idx])] &= !(1<<((compass_idx+2)%4));
+}
+
+// Randomized depth-first search from the center
+pub struct Backtracker;
+
+impl MazeGen for Backtracker {
+	fn carve(&self, routes:&mut Array2<u8>, rng:&mut dyn RngCore) {
+		let routes_bound = maze_bound(routes);
+
+		// Must randomize indices rather than directions because rotation identity matters
+		const COMPASS_IDX:[usize;4] = [2,1,0,3];
+
+		let mut stack = vec![(routes_bound/2, COMPASS_IDX, 0)];
+		while let Some((at, compass_order, compass_order_idx)) = stack.pop() {
+			if compass_order_idx < 3 {
+				stack.push((at, compass_order, compass_order_idx+1));
+			}
+
+			let compass_idx = compass_order[compass_order_idx];
+			let cand = at + DIR_COMPASS[compass_idx];
+
+			if within(cand, routes_bound) {
+				let cand_value = routes[ivec_to_index(cand)];
+				let is_free = cand_value == 0;
+				if is_free {
+					let mut random_compass = COMPASS_IDX;
+					random_compass.shuffle(rng);
+					stack.push((cand, random_compass, 0));
+				}
+				if is_free || 0 != cand_value & 1<<((compass_idx+2)%4) {
+					routes[ivec_to_index(at)] |= 1<<compass_idx; // Reciprocate
+				}
+			}
+		}
+	}
+}
+
+// Grow from the center by opening a random wall on the edge of what's carved so far
+pub struct Prim;
+
+impl MazeGen for Prim {
+	fn carve(&self, routes:&mut Array2<u8>, rng:&mut dyn RngCore) {
+		let routes_bound = maze_bound(routes);
+		let mut carved:Array2<bool> = Array2::default(routes.dim());
+		let start = routes_bound/2;
+		carved[ivec_to_index(start)] = true;
+
+		let mut frontier:Vec<(IVec2, usize)> = (0..4).map(|compass_idx| (start, compass_idx)).collect();
+		while !frontier.is_empty() {
+			let (at, compass_idx) = frontier.swap_remove(rng.gen_range(0..frontier.len() as u32) as usize);
+			let next = at + DIR_COMPASS[compass_idx];
+			if !within(next, routes_bound) || carved[ivec_to_index(next)] { continue }
+
+			carved[ivec_to_index(next)] = true;
+			maze_open(routes, at, compass_idx);
+			frontier.extend((0..4).map(|compass_idx| (next, compass_idx)));
+		}
+	}
+}
+
+// Open every wall in random order, unless the cells either side are already connected
+pub struct Kruskal;
+
+impl MazeGen for Kruskal {
+	fn carve(&self, routes:&mut Array2<u8>, rng:&mut dyn RngCore) {
+		let routes_bound = maze_bound(routes);
+		let cell_idx = |at:IVec2| (at.y*routes_bound.x + at.x) as usize;
+
+		// Only right and down walls, so each is listed once
+		let mut edges:Vec<(IVec2, usize)> = Vec::default();
+		for y in 0..routes_bound.y {
+			for x in 0..routes_bound.x {
+				for compass_idx in [Dir::Right as usize, Dir::Down as usize] {
+					let at = IVec2::new(x, y);
+					if within(at + DIR_COMPASS[compass_idx], routes_bound) { edges.push((at, compass_idx)); }
+				}
+			}
+		}
+		edges.shuffle(rng);
+
+		// Union-find over cells
+		let mut parent:Vec<usize> = (0..(routes_bound.x*routes_bound.y) as usize).collect();
+		fn root(parent:&mut [usize], mut idx:usize) -> usize {
+			while parent[idx] != idx {
+				parent[idx] = parent[parent[idx]];
+				idx = parent[idx];
+			}
+			idx
+		}
+
+		for (at, compass_idx) in edges {
+			let a = root(&mut parent, cell_idx(at));
+			let b = root(&mut parent, cell_idx(at + DIR_COMPASS[compass_idx]));
+			if a != b {
+				parent[a] = b;
+				maze_open(routes, at, compass_idx);
+			}
+		}
+	}
+}
+
+// Random walks from each uncarved cell until they hit the maze, keeping only the last way out of
+// each cell (which erases loops). Slow to start but gives a uniformly random maze
+pub struct Wilson;
+
+impl MazeGen for Wilson {
+	fn carve(&self, routes:&mut Array2<u8>, rng:&mut dyn RngCore) {
+		let routes_bound = maze_bound(routes);
+		let mut carved:Array2<bool> = Array2::default(routes.dim());
+		let mut way_out:Array2<usize> = Array2::default(routes.dim());
+		carved[ivec_to_index(routes_bound/2)] = true;
+
+		for y in 0..routes_bound.y {
+			for x in 0..routes_bound.x {
+				let start = IVec2::new(x, y);
+
+				let mut at = start;
+				while !carved[ivec_to_index(at)] {
+					let compass_idx = loop {
+						let compass_idx = rng.gen_range(0..4u32) as usize;
+						if within(at + DIR_COMPASS[compass_idx], routes_bound) { break compass_idx }
+					};
+					way_out[ivec_to_index(at)] = compass_idx;
+					at += DIR_COMPASS[compass_idx];
+				}
+
+				let mut at = start;
+				while !carved[ivec_to_index(at)] {
+					let compass_idx = way_out[ivec_to_index(at)];
+					carved[ivec_to_index(at)] = true;
+					maze_open(routes, at, compass_idx);
+					at += DIR_COMPASS[compass_idx];
+				}
+			}
+		}
+	}
+}
+
+// Start wide open, then keep splitting chambers with a wall that has one gap in it
+pub struct Division;
+
+impl MazeGen for Division {
+	fn carve(&self, routes:&mut Array2<u8>, rng:&mut dyn RngCore) {
+		let routes_bound = maze_bound(routes);
+		for y in 0..routes_bound.y {
+			for x in 0..routes_bound.x {
+				let at = IVec2::new(x, y);
+				for compass_idx in [Dir::Right as usize, Dir::Down as usize] {
+					if within(at + DIR_COMPASS[compass_idx], routes_bound) { maze_open(routes, at, compass_idx); }
+				}
+			}
+		}
+
+		let mut chambers = vec![(IVec2::ZERO, routes_bound)]; // Origin, size
+		while let Some((origin, size)) = chambers.pop() {
+			if size.x < 2 || size.y < 2 { continue }
+			// Cut across the long side, so chambers stay roughly square
+			let across_y = if size.x != size.y { size.y > size.x } else { rng.gen_range(0..=1u32) == 0 };
+			if across_y {
+				let wall = rng.gen_range(0..size.y-1); // Wall below this row
+				let gap = rng.gen_range(0..size.x);
+				for x in 0..size.x {
+					if x != gap { maze_close(routes, origin + IVec2::new(x, wall), Dir::Down as usize); }
+				}
+				chambers.push((origin, IVec2::new(size.x, wall+1)));
+				chambers.push((origin + IVec2::new(0, wall+1), IVec2::new(size.x, size.y-wall-1)));
+			} else {
+				let wall = rng.gen_range(0..size.x-1); // Wall right of this column
+				let gap = rng.gen_range(0..size.y);
+				for y in 0..size.y {
+					if y != gap { maze_close(routes, origin + IVec2::new(wall, y), Dir::Right as usize); }
+				}
+				chambers.push((origin, IVec2::new(wall+1, size.y)));
+				chambers.push((origin + IVec2::new(wall+1, 0), IVec2::new(size.x-wall-1, size.y)));
+			}
+		}
+	}
+}
+
+// Knock through the end wall of some dead ends, preferring walls onto another dead end
+// (that removes two at once). With percent 0 the rng isn't touched
+pub fn maze_braid(routes:&mut Array2<u8>, percent:u32, rng:&mut dyn RngCore) {
+	if percent == 0 { return }
+	let routes_bound = maze_bound(routes);
+	let dead_end = |routes:&Array2<u8>, at:IVec2| routes[ivec_to_index(at)].count_ones() == 1;
+
+	for y in 0..routes_bound.y {
+		for x in 0..routes_bound.x {
+			let at = IVec2::new(x, y);
+			if !dead_end(routes, at) || rng.gen_range(0..100u32) >= percent { continue } // May have been opened already
+
+			let closed:Vec<usize> = (0..4).filter(|&compass_idx| 0 == routes[ivec_to_index(at)] & 1<<compass_idx
+				&& within(at + DIR_COMPASS[compass_idx], routes_bound)).collect();
+			let onto_dead_end:Vec<usize> = closed.iter().copied()
+				.filter(|&compass_idx| dead_end(routes, at + DIR_COMPASS[compass_idx])).collect();
+			let choices = if onto_dead_end.is_empty() { closed } else { onto_dead_end };
+			if let Some(&compass_idx) = choices.choose(rng) {
+				maze_open(routes, at, compass_idx);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::*;
+
+	// Every generator, braided or not, at shapes other than square: passages are open from both
+	// sides, none lead off the edge, and every cell reaches every other
+	#[test]
+	fn maze_connected() {
+		for (kind, name) in MAZE_KINDS {
+			for size in [IVec2::new(2, 2), IVec2::new(2, 7), IVec2::new(9, 3), IVec2::new(16, 9)] {
+				for (seed, braid) in (0..4).zip([0, 0, 50, 100]) {
+					let mut routes = Array2::default(ivec_to_index(size));
+					maze_carve(&MazeStyle { kind, braid, size, difficulty:None }, &mut routes, &mut game_rng(seed, 0));
+					let what = format!("{} {}x{} seed {} braid {}", name, size.x, size.y, seed, braid);
+
+					for ((y, x), &route) in routes.indexed_iter() {
+						let at = IVec2::new(x as i32, y as i32);
+						for (compass_idx, offset) in DIR_COMPASS.iter().enumerate() {
+							if 0 == route & 1<<compass_idx { continue }
+							let next = at + *offset;
+							assert!(within(next, size), "{}: {} opens off the edge", what, at);
+							assert!(0 != routes[ivec_to_index(next)] & 1<<((compass_idx + 2) % 4), "{}: {} opens one way only", what, at);
+						}
+					}
+					assert!(room_search(&routes, IVec2::ZERO, &[]).iter().all(Option::is_some), "{}: not all connected", what);
+				}
+			}
+		}
+	}
+}
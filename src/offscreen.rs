@@ -0,0 +1,154 @@
+// Rendering without a window, for screenshots on machines with no display or GPU. Same pipeline
+// as the game draws with, into the canvas texture, then copied back to the CPU
+
+use glam::{IVec2, Vec2};
+use image::{GrayImage, RgbaImage};
+
+use crate::blit::*;
+use crate::constants::*;
+use crate::palette::*;
+use crate::quad::*;
+use crate::render::*;
+use crate::room::*;
+use crate::texture::*;
+
+// sRGB, like the swapchain on desktop, so the picture matches the window there. (Browsers give a
+// plain Bgra8Unorm swapchain, where the same colors come out darker)
+const OFFSCREEN_FORMAT:wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+// Software adapter if there is one, since the point is to work anywhere and come out the same.
+// Without one, fail if software_only, otherwise use whatever's around and warn that it's different
+async fn offscreen_adapter(software_only:bool) -> Result<wgpu::Adapter, String> {
+    let instance = wgpu::Instance::default();
+    let mut adapter = None;
+    for force_fallback_adapter in [true, false] {
+        adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter,
+            compatible_surface: None,
+        }).await;
+        if adapter.is_some() { break }
+    }
+    let adapter = adapter.ok_or("No graphics adapter, not even a software one")?;
+    let info = adapter.get_info();
+    if info.device_type != wgpu::DeviceType::Cpu {
+        if software_only { return Err(format!("No software adapter, only {}", info.name)) }
+        log::warn!("No software adapter, so rendering with {}; output may differ from other machines", info.name);
+    }
+    log::info!("Rendering with {:?}", info);
+    Ok(adapter)
+}
+
+async fn offscreen_device(software_only:bool) -> Result<(wgpu::Device, wgpu::Queue), String> {
+    let adapter = offscreen_adapter(software_only).await?;
+    adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+        },
+        None,
+    ).await.map_err(|err| err.to_string())
+}
+
+// Draw the room as the game would (view from room_fit or a camera)
+// and return the CANVAS_SIDE square result. software_only as for offscreen_device
+pub async fn offscreen_render(room:&Room, atlas:GrayImage, palette:&Palette, shading:Shading, view:(Vec2, f32), fog:bool, software_only:bool) -> Result<RgbaImage, String> {
+    let (device, queue) = offscreen_device(software_only).await?;
+
+    let (sprite_atlas, sprite_atlas_view) = make_texture(&device, &queue, atlas, "sprite");
+    let (root_vertex_buffer, root_index_buffer, root_vertex_layout) = make_quad_root_buffer(&device);
+    let (instance_buffer, instance_layout) = make_quad_instance_buffer(&device, "offscreen", room_sprite_count(room));
+    let palette_buffer = make_palette_buffer(&device, &queue, palette);
+    let (pipelines, bind_group_layout) = make_quad_pipelines(&device, OFFSCREEN_FORMAT, root_vertex_layout, instance_layout);
+    let bind_group = make_quad_bind_group(&device, &bind_group_layout, &sprite_atlas_view, &palette_buffer);
+    let (canvas, canvas_view) = make_canvas_texture(&device, OFFSCREEN_FORMAT);
+
+    let atlas_size = IVec2::new(sprite_atlas.width() as i32, sprite_atlas.height() as i32);
+    let sprites = room_render(room, &queue, &instance_buffer, IVec2::splat(CANVAS_SIDE as i32), atlas_size, &RoomFrame { view, fog, tween:None, actor_draw:true });
+
+    // Rows of a texture copy have to be padded out to a multiple of 256 bytes
+    let row = CANVAS_SIDE*4;
+    let padded_row = (row + wgpu::COPY_BYTES_PER_ROW_ALIGNMENT - 1) / wgpu::COPY_BYTES_PER_ROW_ALIGNMENT * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback"),
+        size: (padded_row*CANVAS_SIDE) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let [r, g, b] = palette_color(palette.paper).map(|channel| channel as f64);
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &canvas_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a:1. }),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&pipelines[shading as usize]);
+        rpass.set_vertex_buffer(0, root_vertex_buffer.slice(..));
+        rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+        rpass.set_index_buffer(root_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw_indexed(0..6, 0, 0..(sprites as u32));
+    }
+    encoder.copy_texture_to_buffer(
+        canvas.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(padded_row), rows_per_image: None },
+        },
+        canvas.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| { let _ = sender.send(result); });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().map_err(|err| err.to_string())?.map_err(|err| err.to_string())?;
+
+    let mapped = slice.get_mapped_range();
+    let pixels = mapped.chunks(padded_row as usize).flat_map(|padded| &padded[..row as usize]).copied().collect();
+    Ok(RgbaImage::from_raw(CANVAS_SIDE, CANVAS_SIDE, pixels).expect("Readback is the canvas size"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::*;
+
+    // A known room through the whole pipeline in each shading, compared with pictures of it that
+    // looked right (so they change when rooms do too). Needs a software renderer (llvmpipe or
+    // similar) to come out the same, so without one there's nothing to check
+    #[test]
+    fn offscreen_golden() {
+        if let Err(err) = pollster::block_on(offscreen_adapter(true)) {
+            eprintln!("Skipping offscreen_golden: {}", err);
+            return
+        }
+        let mut state = GameState::new(1234);
+        state.level = 4;
+        let room = game_room(&mut state);
+        let atlas = pollster::block_on(load_sprite_atlas(&mut game_rng(state.seed, ATLAS_RNG_STREAM)));
+        let palette = &PALETTES[palette_parse("color").unwrap()];
+        for (shading, name) in SHADINGS {
+            let image = pollster::block_on(offscreen_render(&room, atlas.clone(), palette, shading, room_fit(&room), false, true)).unwrap();
+
+            let file = format!("offscreen_1234_{}.png", name);
+            let golden = image::open(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(&file)).unwrap().into_rgba8();
+            if image != golden {
+                let path = std::env::temp_dir().join(&file);
+                image.save(&path).unwrap();
+                panic!("Doesn't match testdata/{}; got {}", file, path.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// Color themes. The atlas is gray: black ink on white paper. The shader paints paper in the
+// palette's paper color and ink in the tint for whatever's being drawn, so gray levels in between
+// land between the two
+
+use crate::constants::*;
+
+// Which tint a sprite's ink takes. Keys and gates of a kind share a color
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tint {
+	Wall = 0,
+	Player,
+	Door,
+	Key, // Plain; colored kinds follow in order
+	KeyRed,
+	KeyGreen,
+	KeyBlue,
+	Ammo,
+	Shot,
+	Monster,
+}
+
+pub const TINT_COUNT:usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+	pub name: &'static str,
+	pub paper: u32, // 0xRRGGBB
+	pub tints: [u32; TINT_COUNT], // Indexed by Tint
+}
+
+pub const PALETTES:[Palette; 4] = [
+	// The original look: black on cream
+	Palette { name:"classic", paper:0xFAECD1, tints:[0x000000; TINT_COUNT] },
+	Palette { name:"color", paper:0xFAECD1, tints:[
+		0x3B3B58, 0x1F6FB2, 0x8B4513, 0x7A6000, 0xC0392B, 0x2E8B57, 0x2A52BE, 0x6B4F9E, 0xD35400, 0x8E1B1B
+	] },
+	// Bright on black
+	Palette { name:"high-contrast", paper:0x000000, tints:[
+		0xFFFFFF, 0x00FFFF, 0xFFFFFF, 0xFFFF00, 0xFF4040, 0x40FF40, 0x6090FF, 0xFF80FF, 0xFFFFFF, 0xFF8000
+	] },
+	// Okabe and Ito's set, told apart under the common kinds of color blindness
+	Palette { name:"colorblind", paper:0xFFFFFF, tints:[
+		0x000000, 0x0072B2, 0x000000, 0xE69F00, 0xD55E00, 0x009E73, 0x56B4E9, 0x000000, 0x000000, 0xCC79A7
+	] },
+];
+
+pub fn palette_parse(name:&str) -> Option<usize> {
+	PALETTES.iter().position(|palette| palette.name == name)
+}
+
+pub fn palette_color(rgb:u32) -> [f32; 3] {
+	[(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8].map(|channel| channel as f32/255.)
+}
+
+// Laid out for the shader's Palette struct: paper, then each tint, as vec4s
+pub fn palette_uniform(palette:&Palette) -> [[f32; 4]; 1+TINT_COUNT] {
+	let mut uniform = [[1.; 4]; 1+TINT_COUNT];
+	for (slot, rgb) in std::iter::once(palette.paper).chain(palette.tints).enumerate() {
+		uniform[slot][..3].copy_from_slice(&palette_color(rgb));
+	}
+	uniform
+}
+
+pub fn actor_tint(actor:&Actor) -> Tint {
+	const KEY_TINTS:[Tint; KEY_KINDS as usize] = [Tint::Key, Tint::KeyRed, Tint::KeyGreen, Tint::KeyBlue];
+	match actor {
+		Actor::Player(_) => Tint::Player,
+		Actor::Door => Tint::Door,
+		Actor::Key(_, kind) | Actor::Gate(kind) => KEY_TINTS[*kind as usize],
+		Actor::Ammo => Tint::Ammo,
+		Actor::Shot(_) => Tint::Shot,
+		Actor::Monster(..) => Tint::Monster,
+	}
+}
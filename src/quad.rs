@@ -1,9 +1,11 @@
 // Basic routines for working with textured quads
 
-use std::mem;
+use std::{borrow::Cow, mem};
 use wgpu::util::DeviceExt;
 
 use crate::constants::*;
+use crate::palette::*;
+use crate::render::*;
 
 const SQUARE_VERTEX : [f32;8] = [
     0., 0.,
@@ -43,7 +45,7 @@ pub fn make_quad_root_buffer(device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buff
 }
 
 const QUAD_INSTANCE_LAYOUT : wgpu::VertexBufferLayout = wgpu::VertexBufferLayout {
-    array_stride: (mem::size_of::<f32>()*8) as wgpu::BufferAddress,
+    array_stride: SPRITE_SIZE as wgpu::BufferAddress,
     step_mode: wgpu::VertexStepMode::Instance,
     attributes: &[
         wgpu::VertexAttribute {
@@ -66,17 +68,131 @@ const QUAD_INSTANCE_LAYOUT : wgpu::VertexBufferLayout = wgpu::VertexBufferLayout
             offset: 6*mem::size_of::<f32>() as u64,
             shader_location: 4,
         },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32,
+            offset: 8*mem::size_of::<f32>() as u64,
+            shader_location: 5,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Uint32,
+            offset: SPRITE_TINT_OFFSET,
+            shader_location: 6,
+        },
     ],
 };
 
-// Makes some assumptions about usage
-pub fn make_quad_instance_buffer(device:&wgpu::Device, tag:&str) -> (wgpu::Buffer, wgpu::VertexBufferLayout<'static>) {
+// Makes some assumptions about usage. Holds `sprites` instances
+pub fn make_quad_instance_buffer(device:&wgpu::Device, tag:&str, sprites:u64) -> (wgpu::Buffer, wgpu::VertexBufferLayout<'static>) {
     let instance = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some(&format!("Instance buffer {}", tag)),
-        size: SPRITE_SIZE*SPRITES_MAX,
+        size: SPRITE_SIZE*sprites,
         usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation:false
     });
 
     (instance, QUAD_INSTANCE_LAYOUT)
 }
+
+// One pipeline per entry in SHADINGS (so index them with shading as usize), drawing to the given format,
+// and the bind group layout they share
+pub fn make_quad_pipelines(device:&wgpu::Device, format:wgpu::TextureFormat, root_vertex_layout:wgpu::VertexBufferLayout<'static>, instance_layout:wgpu::VertexBufferLayout<'static>) -> (Vec<wgpu::RenderPipeline>, wgpu::BindGroupLayout) {
+    // Load the shaders from disk
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+    });
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("single bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false }, /* FIXME: Is nearest a filter? */
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ],
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("single pipeline"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let pipelines = SHADINGS.iter().map(|&(shading, _)| {
+        let (entry_point, blend) = match shading {
+            Shading::Direct => ("fs_quad_direct", None),
+            Shading::Threshold => ("fs_quad_threshold", None),
+            Shading::Blend => ("fs_quad_blend", Some(wgpu::BlendState::ALPHA_BLENDING)),
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_quad",
+                buffers: &[root_vertex_layout.clone(), instance_layout.clone()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState { format, blend, write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..wgpu::PrimitiveState::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }).collect();
+
+    (pipelines, bind_group_layout)
+}
+
+// Uniform buffer for the shader's Palette, filled in with this palette
+pub fn make_palette_buffer(device:&wgpu::Device, queue:&wgpu::Queue, palette:&Palette) -> wgpu::Buffer {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Palette"),
+        size: mem::size_of::<[[f32; 4]; 1+TINT_COUNT]>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&palette_uniform(palette)));
+    buffer
+}
+
+pub fn make_quad_bind_group(device:&wgpu::Device, layout:&wgpu::BindGroupLayout, atlas_view:&wgpu::TextureView, palette_buffer:&wgpu::Buffer) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(atlas_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: palette_buffer.as_entire_binding(),
+            },
+        ],
+        layout,
+        label: Some("frame bind group"),
+    })
+}
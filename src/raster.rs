@@ -0,0 +1,161 @@
+// Software stand-in for the GPU: draws the instance records room_sprites makes, the way vs_quad and
+// the fs_quad_* shaders would, so instance generation can be checked without a graphics device.
+// Also how F12 exports a picture of the room
+
+use crate::constants::*;
+use crate::palette::*;
+use crate::render::*;
+use crate::room::*;
+
+use glam::{IVec2, Vec2, Vec3};
+use image::{GrayImage, Rgba, RgbaImage};
+
+// One record: pos_basis, pos_size, tex_basis, tex_size (all 0..1 of the canvas or atlas), brightness, tint (an index into the palette)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+	pub pos_basis: Vec2,
+	pub pos_size: Vec2,
+	pub tex_basis: Vec2,
+	pub tex_size: Vec2, // Negative x reads the atlas right to left, which is how sprites are mirrored
+	pub brightness: f32,
+	pub tint: u32,
+}
+
+// Split an instance buffer back into records
+pub fn raster_records(sprites:&[u8]) -> Vec<Sprite> {
+	sprites.chunks_exact(SPRITE_SIZE as usize).map(|record| {
+		let (floats, tint) = record.split_at(SPRITE_TINT_OFFSET as usize);
+		let f:[f32; 9] = bytemuck::pod_read_unaligned(floats);
+		Sprite {
+			pos_basis: Vec2::new(f[0], f[1]),
+			pos_size: Vec2::new(f[2], f[3]),
+			tex_basis: Vec2::new(f[4], f[5]),
+			tex_size: Vec2::new(f[6], f[7]),
+			brightness: f[8],
+			tint: bytemuck::pod_read_unaligned(tint),
+		}
+	}).collect()
+}
+
+// The canvas is sRGB, as in offscreen.rs: what the shaders write is encoded and rounded to the
+// nearest step, and blending decodes what's already there first
+fn raster_store(color:Vec3) -> Rgba<u8> {
+	let encode = |linear:f32| if linear <= 0.0031308 { linear*12.92 } else { 1.055*linear.powf(1./2.4) - 0.055 };
+	Rgba([color.x, color.y, color.z, 1.].map(|channel| (encode(channel.clamp(0., 1.))*255.).round() as u8))
+}
+
+fn raster_load(pixel:&Rgba<u8>) -> Vec3 {
+	let decode = |encoded:f32| if encoded <= 0.04045 { encoded/12.92 } else { ((encoded + 0.055)/1.055).powf(2.4) };
+	Vec3::from_array([pixel[0], pixel[1], pixel[2]].map(|channel| decode(channel as f32/255.)))
+}
+
+// Draw instance records (as from room_sprites) over paper, on a canvas of the given size
+pub fn raster_sprites(sprites:&[u8], atlas:&GrayImage, palette:&Palette, shading:Shading, size:IVec2) -> RgbaImage {
+	let rgb = |rgb:u32| Vec3::from_array(palette_color(rgb));
+	let paper = rgb(palette.paper);
+	let mut image = RgbaImage::from_pixel(size.x as u32, size.y as u32, raster_store(paper));
+	let canvas = size.as_vec2();
+	let atlas_size = IVec2::new(atlas.width() as i32, atlas.height() as i32);
+
+	for sprite in raster_records(sprites) {
+		let ink = rgb(palette.tints[(sprite.tint as usize).min(TINT_COUNT-1)]);
+
+		// Pixels whose centers fall inside the quad
+		let corner_a = sprite.pos_basis*canvas;
+		let corner_b = (sprite.pos_basis + sprite.pos_size)*canvas;
+		let low = (corner_a.min(corner_b) - 0.5).ceil().as_ivec2().max(IVec2::ZERO);
+		let high = (corner_a.max(corner_b) - 0.5).ceil().as_ivec2().min(size);
+
+		for y in low.y..high.y {
+			for x in low.x..high.x {
+				// Interpolate like the vertex outputs would, then load like textureLoad(vec2<u32>(coord*dim))
+				let position = ((IVec2::new(x, y).as_vec2() + 0.5)/canvas - sprite.pos_basis)/sprite.pos_size;
+				let tex_coord = sprite.tex_basis + position*sprite.tex_size;
+				let texel = (tex_coord*atlas_size.as_vec2()).as_uvec2().min((atlas_size - 1).as_uvec2());
+				let v = atlas.get_pixel(texel.x, texel.y)[0] as f32/255.;
+
+				let pixel = image.get_pixel_mut(x as u32, y as u32);
+				let faded = |v:f32| paper.lerp(ink.lerp(paper, v), sprite.brightness);
+				*pixel = match shading {
+					Shading::Direct => raster_store(faded(v)),
+					Shading::Threshold if v > 0.5 => continue,
+					Shading::Threshold => raster_store(faded(v)),
+					Shading::Blend => raster_store(raster_load(pixel).lerp(faded(0.), 1. - v)), // Alpha blending, alpha 1-v
+				};
+			}
+		}
+	}
+	image
+}
+
+// The whole room, one pixel per room pixel and cropped to it, with everything shown regardless of fog
+pub fn raster_room(room:&Room, atlas:&GrayImage, palette:&Palette, shading:Shading) -> RgbaImage {
+	let (height, width) = room.walls.dim();
+	let size = IVec2::new(width as i32, height as i32)*TILE_SIDE as i32;
+	let atlas_size = IVec2::new(atlas.width() as i32, atlas.height() as i32);
+	let sprites = room_sprites(room, room_sprite_count(room), size, atlas_size, &RoomFrame { view:(Vec2::ZERO, 1.), fog:false, tween:None, actor_draw:true });
+	raster_sprites(&sprites, atlas, palette, shading, size)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::*;
+	use crate::level::*;
+	use crate::texture::*;
+
+	fn raster_atlas() -> GrayImage {
+		pollster::block_on(load_sprite_atlas(&mut game_rng(1234, ATLAS_RNG_STREAM)))
+	}
+
+	// The player sprite, facing either way, lands on its cell and reads the atlas forwards or backwards
+	#[test]
+	fn raster_player_sprites() {
+		let atlas = raster_atlas();
+		let palette = &PALETTES[palette_parse("classic").unwrap()];
+		let (ink, paper) = (Vec3::from_array(palette_color(palette.tints[Tint::Player as usize])), Vec3::from_array(palette_color(palette.paper)));
+		for glyph in ['>', '<'] {
+			let room = level_read(&format!("+-+-+\n|{} D|\n+-+-+\n", glyph)).unwrap();
+			let (player, player_at) = room.actors[room_player(&room).unwrap()];
+			let (sprite_at, reflect) = actor_sprite(&player);
+			assert_eq!(reflect, glyph == '<');
+
+			let image = raster_room(&room, &atlas, palette, Shading::Direct);
+			assert_eq!((image.width(), image.height()), (3*TILE_SIDE, 2*TILE_SIDE));
+			let origin = player_at*TILE_SIDE as i32 + 6;
+			for y in 0..ACTOR_SIDE as i32 {
+				for x in 0..ACTOR_SIDE as i32 {
+					let from = sprite_at + IVec2::new(if reflect { ACTOR_SIDE as i32 - 1 - x } else { x }, y);
+					let v = atlas.get_pixel(from.x as u32, from.y as u32)[0] as f32/255.;
+					assert_eq!(*image.get_pixel((origin.x + x) as u32, (origin.y + y) as u32), raster_store(ink.lerp(paper, v)),
+						"{} at {}, {}", glyph, x, y);
+				}
+			}
+		}
+	}
+
+	// The room offscreen.rs checks on the GPU comes out the same here, in every shading
+	#[test]
+	fn raster_matches_offscreen() {
+		let mut state = GameState::new(1234);
+		state.level = 4;
+		let room = game_room(&mut state);
+		let atlas = raster_atlas();
+		let palette = &PALETTES[palette_parse("color").unwrap()];
+		let canvas = IVec2::splat(CANVAS_SIDE as i32);
+		let atlas_size = IVec2::new(atlas.width() as i32, atlas.height() as i32);
+		let sprites = room_sprites(&room, room_sprite_count(&room), canvas, atlas_size, &RoomFrame { view:room_fit(&room), fog:false, tween:None, actor_draw:true });
+		for (shading, name) in SHADINGS {
+			let image = raster_sprites(&sprites, &atlas, palette, shading, canvas);
+
+			let file = format!("offscreen_1234_{}.png", name);
+			let golden = image::open(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata").join(&file)).unwrap().into_rgba8();
+			// The GPU's sRGB encoding can round the other way from ours, so off by one is fine
+			assert_eq!(image.dimensions(), golden.dimensions());
+			for (x, y, pixel) in image.enumerate_pixels() {
+				let expect = golden.get_pixel(x, y);
+				assert!(pixel.0.iter().zip(expect.0).all(|(&a, b)| a.abs_diff(b) <= 1), "{}: {:?} at {}, {}, expected {:?}", name, pixel, x, y, expect);
+			}
+		}
+	}
+}
@@ -0,0 +1,186 @@
+// Drawing a Room as textured quads
+
+use crate::constants::*;
+use crate::palette::*;
+use crate::room::*;
+use crate::slots::*;
+use crate::tween::*;
+
+use glam::{IVec2, Vec2};
+use ndarray::Axis;
+
+fn make_float(v:IVec2, scale:Vec2) -> [f32;2] {
+	(
+		Vec2::new(v.x as f32, v.y as f32)
+		/ scale
+	).to_array()
+}
+
+// How sprites go over what's under them: fully opaque, ink only, or ink blended by gray level.
+// Each has its own fragment shader (see quad.rs), which raster.rs copies
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Shading {
+	Direct,
+	Threshold,
+	Blend,
+}
+
+pub const SHADINGS:[(Shading, &str); 3] = [
+	(Shading::Direct, "direct"),
+	(Shading::Threshold, "threshold"),
+	(Shading::Blend, "blend"),
+];
+
+pub fn shading_parse(name:&str) -> Option<Shading> {
+	SHADINGS.iter().find(|&&(_, shading_name)| shading_name == name).map(|&(shading, _)| shading)
+}
+
+// Where the room's top left goes on the canvas, and how much it's shrunk to fit (1 if it fits as is).
+// Either way it's centered
+pub fn room_fit(room:&Room) -> (Vec2, f32) {
+	let (height, width) = room.walls.dim();
+	let extent = Vec2::new(width as f32, height as f32) * TILE_SIDE as f32;
+	let canvas = Vec2::splat(CANVAS_SIDE as f32);
+	let scale = (canvas / extent).min_element().min(1.);
+	((canvas - extent*scale)/2., scale)
+}
+
+// Where an actor's sprite is in the atlas (top left, in pixels), and whether to mirror it horizontally
+pub fn actor_sprite(actor:&Actor) -> (IVec2, bool) {
+	let actor_at = |which:u32| IVec2::new((which*ACTOR_SIDE) as i32, ACTOR_Y_ORIGIN as i32);
+	let kind_at = |slot:u32, kind:u8| IVec2::new(((slot*KEY_KINDS + kind as u32)*ACTOR_SIDE) as i32, KIND_Y_ORIGIN as i32);
+	match actor {
+		Actor::Player(Dir::Right) => (actor_at(0), false),
+		Actor::Player(Dir::Left) => (actor_at(0), true),
+		Actor::Player(Dir::Down) => (actor_at(1), false),
+		Actor::Player(Dir::Up) => (actor_at(2), false),
+		Actor::Door => (actor_at(3), false),
+		Actor::Key(true, kind) => (kind_at(0, *kind), false),
+		Actor::Key(false, kind) => (kind_at(1, *kind), false),
+		Actor::Gate(kind) => (kind_at(2, *kind), false),
+		Actor::Shot(_) => (actor_at(6), false),
+		Actor::Ammo => (actor_at(7), false),
+		Actor::Monster(_, n) => (actor_at(8+*n as u32), false), // FIXME: Must assert MONSTER_Y_ORIGIN == 0, MONSTER_X_ORIGIN == 8*8
+	}
+}
+
+// Instances room_render wants for this room. Shots come and go, so leave some slack
+pub fn room_sprite_count(room:&Room) -> u64 {
+	((room.walls.len() + room.actors.len()) as u64 * 5/4).max(SPRITES_MAX)
+}
+
+// How a room is drawn this frame, apart from the canvas and atlas sizes.
+// view is where the room's top left goes on the canvas and its scale, from room_fit or camera_origin.
+// With fog, only what the player can see now is drawn fully, remembered cells are faded, and the rest
+// is left out. With a tween, actors are drawn partway along from where they were
+#[derive(Clone, Copy)]
+pub struct RoomFrame<'a> {
+	pub view:(Vec2, f32),
+	pub fog:bool,
+	pub tween:Option<&'a Tween>,
+	pub actor_draw:bool,
+}
+
+// Write the room's instances to buffer, returning how many there are. See room_sprites
+#[cfg(feature = "graphics")]
+pub fn room_render(room: &Room, queue: &wgpu::Queue, buffer: &wgpu::Buffer, pos_scale:IVec2, tex_scale:IVec2, frame:&RoomFrame) -> u64 {
+	let storage = room_sprites(room, buffer.size() / SPRITE_SIZE, pos_scale, tex_scale, frame);
+	queue.write_buffer(buffer, 0, &storage);
+	storage.len() as u64 / SPRITE_SIZE
+}
+
+// Instance records for the room, as bytes ready for the instance buffer; at most sprites_max of them.
+// pos_scale is the canvas size in pixels (usually CANVAS_SIDE square) and tex_scale the atlas's.
+// Anything entirely off the canvas is skipped
+pub fn room_sprites(room: &Room, sprites_max:u64, pos_scale:IVec2, tex_scale:IVec2, frame:&RoomFrame) -> Vec<u8> {
+	// Notice y,x order
+	const TILE_SIZE:IVec2 = IVec2::new(TILE_SIDE as i32, TILE_SIDE as i32);
+
+	let &RoomFrame { view:(origin, fit), fog, tween, actor_draw } = frame;
+	let (pos_scale, tex_scale) = (pos_scale.as_vec2(), tex_scale.as_vec2());
+	let onscreen = |v:IVec2, size:IVec2| {
+		let (at, size) = (origin + v.as_vec2()*fit, size.as_vec2()*fit);
+		(at + size).cmpgt(Vec2::ZERO).all() && at.cmplt(pos_scale).all()
+	};
+	let tex_scale_reflect = Vec2::new(-tex_scale.x, tex_scale.y);
+
+	// Make position, make size (both from room pixels), make tile
+	let mp = |v:IVec2| { ((origin + v.as_vec2()*fit) / pos_scale).to_array() };
+	let ms = |v:IVec2| { make_float(v, pos_scale/fit) };
+	let mt = |v:IVec2, reflect:bool| { make_float(v, if reflect { tex_scale_reflect } else { tex_scale }) };
+
+	// Brightness of a cell, or None to skip it
+	let visible = room_visible(room);
+	let light = |at:IVec2| {
+		     if !fog || visible[ivec_to_index(at)] { Some(1.) }
+		else if room.seen[ivec_to_index(at)] { Some(FOG_REMEMBERED) }
+		else { None }
+	};
+	let (height, width) = room.routes.dim();
+	let routes_bound = IVec2::new(width as i32, height as i32);
+
+	let mut storage:Vec<u8> = Vec::default(); 
+
+	'grid: for (y,col) in room.walls.axis_iter(Axis(0)).enumerate() {
+		for (x,&tile_which) in col.iter().enumerate() {
+			let tile_which = tile_which as u32; // Notice y,x order
+			let at = IVec2::new((x as u32*TILE_SIDE) as i32, (y as u32*TILE_SIDE) as i32);
+			if !onscreen(at, TILE_SIZE) { continue }
+			// A wall tile sits on the corner of up to four cells, and is as bright as the brightest
+			let corner = IVec2::new(x as i32, y as i32);
+			let Some(brightness) = [IVec2::new(-1,-1), IVec2::new(0,-1), IVec2::new(-1,0), IVec2::ZERO].iter()
+				.map(|&offset| corner + offset)
+				.filter(|&cell| within(cell, routes_bound))
+				.filter_map(light)
+				.reduce(f32::max) else { continue };
+			let sprite = [
+				mp(at),
+				ms(TILE_SIZE),
+				mt(IVec2::new(((tile_which%TILE_ROW_MAX)*TILE_SIDE) as i32, (TILE_Y_ORIGIN+(tile_which/TILE_ROW_MAX)*TILE_SIDE) as i32), false),
+				mt(TILE_SIZE, false)
+			];
+
+//			assert!(mem::size_of_val(&sprite) as u64 == SPRITE_SIZE);
+
+			if storage.len() as u64 + SPRITE_SIZE > sprites_max*SPRITE_SIZE { break 'grid }
+
+			storage.extend_from_slice(bytemuck::bytes_of(&sprite));
+			storage.extend_from_slice(bytemuck::bytes_of(&brightness));
+			storage.extend_from_slice(bytemuck::bytes_of(&(Tint::Wall as u32)));
+		}
+	}
+
+	if actor_draw {
+		const ACTOR_SIZE:IVec2 = IVec2::new(ACTOR_SIDE as i32, ACTOR_SIDE as i32);
+
+		// Player last, so it's on top of anything it's standing over
+		let mut actors:Vec<(Handle, &(Actor, IVec2))> = room.actors.iter().collect();
+		actors.sort_by_key(|(_, (actor, _))| matches!(actor, Actor::Player(_)));
+		'sprite: for (handle, (actor, at)) in actors {
+			let (sprite_at, reflect) = actor_sprite(actor);
+			let Some(brightness) = light(*at) else { continue };
+			if brightness < 1. && matches!(actor, Actor::Monster(..) | Actor::Shot(_)) { continue } // Only remember things that stay put
+			let at = match tween { Some(tween) => tween_at(tween, handle, *at), None => at.as_vec2() };
+			let at = (at*TILE_SIDE as f32).round().as_ivec2() + 6; // Whole pixels, like the camera
+			if !onscreen(at, ACTOR_SIZE) { continue }
+			let sprite = [
+				mp(at),
+				ms(ACTOR_SIZE),
+				mt(sprite_at + IVec2::new(if reflect { ACTOR_SIDE as i32 } else { 0 }, 0), false), // Reflected starts from the right edge
+				mt(ACTOR_SIZE, reflect)
+			];
+
+//			assert!(mem::size_of_val(&sprite) as u64 == SPRITE_SIZE);
+
+			if storage.len() as u64 + SPRITE_SIZE > sprites_max*SPRITE_SIZE { break 'sprite }
+
+			storage.extend_from_slice(bytemuck::bytes_of(&sprite));
+			storage.extend_from_slice(bytemuck::bytes_of(&brightness));
+			storage.extend_from_slice(bytemuck::bytes_of(&(actor_tint(actor) as u32)));
+		}
+	}
+
+//	println!("FLOOR LEN {} ({})", storage.len(), storage.len() as u64 / SPRITE_SIZE);
+
+	storage
+}
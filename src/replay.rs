@@ -0,0 +1,163 @@
+// Recorded sessions: the starting seed plus every input, as text so they can go in bug reports.
+//
+//   zap-replay 3
+//   seed 1234
+//   history 1000
+//   maze prim 30 11 11
+//   RRDLLFUZY
+//   N5678
+//   DDR
+//
+// R/D/L/U move, F fire, W wait (a realtime tick), Z undo, Y redo, N<seed> reset. Whitespace between inputs is ignored.
+// Replays only play back the same if every seed makes the same rooms, so the version goes up whenever
+// room generation changes, and older replays are turned away rather than played wrong.
+
+use crate::constants::*;
+use crate::maze::*;
+use crate::session::*;
+
+const REPLAY_HEADER:&str = "zap-replay 3";
+const REPLAY_LINE_MAX:usize = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Replay {
+	pub seed: u64,
+	pub history_depth: usize, // Undo depth affects outcome, so must match
+	pub maze: MazeStyle,
+	pub inputs: Vec<Input>,
+}
+
+pub fn replay_write(replay:&Replay) -> String {
+	let mut out = format!("{}\nseed {}\nhistory {}\nmaze {}\n", REPLAY_HEADER, replay.seed, replay.history_depth,
+		maze_style_write(&replay.maze));
+	let mut line_len = 0;
+	for &input in &replay.inputs {
+		replay_write_input(&mut out, &mut line_len, input);
+	}
+	if line_len > 0 { out.push('\n'); }
+	out
+}
+
+// What replay_write puts down for the latest input, so a recording can be appended to as it goes
+// rather than rewritten every time (the last line is left unfinished, which reads back the same)
+pub fn replay_write_last(replay:&Replay) -> String {
+	let mut out = String::new();
+	let Some((&last, before)) = replay.inputs.split_last() else { return out };
+	let since_reset = before.iter().rev().take_while(|input| !matches!(input, Input::Reset(_))).count();
+	let mut line_len = since_reset % REPLAY_LINE_MAX;
+	replay_write_input(&mut out, &mut line_len, last);
+	out
+}
+
+// One input's token; line_len counts tokens on the current line
+fn replay_write_input(out:&mut String, line_len:&mut usize, input:Input) {
+	let token = match input {
+		Input::Move(dir) => DIR_LETTER[dir as usize],
+		Input::Fire => 'F',
+		Input::Wait => 'W',
+		Input::Undo => 'Z',
+		Input::Redo => 'Y',
+		Input::Reset(seed) => { // Own line, for readability
+			if *line_len > 0 { out.push('\n'); }
+			out.push_str(&format!("N{}\n", seed));
+			*line_len = 0;
+			return
+		}
+	};
+	out.push(token);
+	*line_len += 1;
+	if *line_len >= REPLAY_LINE_MAX { out.push('\n'); *line_len = 0; }
+}
+
+pub fn replay_read(text:&str) -> Result<Replay, String> {
+	let mut lines = text.lines().enumerate();
+
+	let mut field = |name:&str| -> Result<String, String> {
+		let (idx, line) = lines.next().ok_or_else(|| format!("Replay ends before \"{}\"", name))?;
+		line.strip_prefix(name).and_then(|value| value.strip_prefix(' '))
+			.map(String::from)
+			.ok_or_else(|| format!("Line {}: expected \"{} ...\"", idx+1, name))
+	};
+	let version = field("zap-replay")?;
+	if format!("zap-replay {}", version) != REPLAY_HEADER {
+		return Err(format!("Unsupported replay version {}; rooms are made differently now, so it wouldn't play back the same", version))
+	}
+	let seed = field("seed")?;
+	let seed = seed.parse().map_err(|_| format!("Bad seed \"{}\"", seed))?;
+	let history_depth = field("history")?;
+	let history_depth = history_depth.parse().map_err(|_| format!("Bad history depth \"{}\"", history_depth))?;
+	let maze = field("maze")?;
+	let maze = maze_style_read(&maze).ok_or_else(|| format!("Bad maze \"{}\"", maze))?;
+
+	let mut inputs = Vec::default();
+	for (idx, line) in lines {
+		let mut chars = line.chars().peekable();
+		while let Some(c) = chars.next() {
+			if let Some(dir) = letter_to_dir(c) {
+				inputs.push(Input::Move(dir));
+				continue
+			}
+			inputs.push(match c {
+				'F' => Input::Fire,
+				'W' => Input::Wait,
+				'Z' => Input::Undo,
+				'Y' => Input::Redo,
+				'N' => {
+					let mut digits = String::new();
+					while let Some(&digit) = chars.peek() {
+						if !digit.is_ascii_digit() { break }
+						digits.push(digit);
+						chars.next();
+					}
+					Input::Reset(digits.parse().map_err(|_| format!("Line {}: reset needs a seed", idx+1))?)
+				},
+				c if c.is_whitespace() => continue,
+				c => return Err(format!("Line {}: unknown input '{}'", idx+1, c))
+			});
+		}
+	}
+
+	Ok(Replay { seed, history_depth, maze, inputs })
+}
+
+// Run a replay from the start through the same path as live input
+pub fn replay_play(replay:&Replay) -> Session {
+	let mut session = session_make(replay.seed, replay.maze, replay.history_depth, false);
+	for &input in &replay.inputs {
+		session_input(&mut session, input);
+	}
+	session
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::save::*;
+
+	#[test]
+	fn replay_round_trip() {
+		let mut inputs:Vec<Input> = (0..150).map(|idx| Input::Move(DIR_ALL[idx % 4])).collect();
+		inputs.extend([Input::Fire, Input::Wait, Input::Undo, Input::Redo, Input::Reset(5678), Input::Move(Dir::Up), Input::Reset(u64::MAX)]);
+		let replay = Replay { seed:1234, history_depth:50, maze:MazeStyle { braid:30, ..MazeStyle::default() }, inputs };
+		assert_eq!(replay_read(&replay_write(&replay)).unwrap(), replay);
+
+		// Written a bit at a time, as --record does
+		let mut partial = Replay { inputs:Vec::default(), ..replay.clone() };
+		let mut text = replay_write(&partial);
+		for &input in &replay.inputs {
+			partial.inputs.push(input);
+			text.push_str(&replay_write_last(&partial));
+		}
+		assert_eq!(replay_read(&text).unwrap(), replay);
+	}
+
+	// A recorded game (through level 0, with fire, waits, undo and redo along the way) has to end up
+	// exactly where it did when recorded. Changes to rooms or rules break this on purpose: bump
+	// REPLAY_HEADER if rooms changed, then record the files in testdata again
+	#[test]
+	fn replay_plays_back() {
+		let replay = replay_read(include_str!("../testdata/replay_1265.txt")).unwrap();
+		let session = replay_play(&replay);
+		assert_eq!(save_write(&session.state, &session.room), include_str!("../testdata/replay_1265_save.txt"));
+	}
+}
@@ -1,25 +1,56 @@
 // Data structure for a world map tile
 
 use std::cmp::Reverse;
+use std::collections::VecDeque;
 
 use crate::constants::*;
+use crate::maze::*;
+use crate::slots::*;
+use crate::solve::*;
 
 //use std::mem;
-use glam::{IVec2, Vec2};
+use glam::IVec2;
 use ndarray::{Array2, Axis};
 use rand::{seq::SliceRandom, Rng};
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Room {
 	pub routes:Array2<u8>,
 	pub walls:Array2<u8>,
-	pub actors:Vec<(Actor, IVec2)> // Data, location
+	pub actors:Slots<(Actor, IVec2)>, // Data, location
+	pub seen:Array2<bool>, // Cells the player has been able to see at some point
 }
 
-fn make_float(v:IVec2, scale:Vec2) -> [f32;2] {
-	(
-		Vec2::new(v.x as f32, v.y as f32)
-		/ scale
-	).to_array()
+// The player's handle, if there is one
+pub fn room_player(room:&Room) -> Option<Handle> {
+	room.actors.iter().find_map(|(handle, (actor, _))| matches!(actor, Actor::Player(_)).then_some(handle))
+}
+
+// Actors standing on a cell
+pub fn room_actors_at(room:&Room, at:IVec2) -> impl Iterator<Item=(Handle, &Actor)> {
+	room.actors.iter().filter_map(move |(handle, (actor, actor_at))| (*actor_at == at).then_some((handle, actor)))
+}
+
+// Cells the player can see right now: their own, and straight down open passages each way
+pub fn room_visible(room:&Room) -> Array2<bool> {
+	let mut visible:Array2<bool> = Array2::default(room.routes.dim());
+	let Some(player) = room_player(room) else { return visible };
+	let from = room.actors[player].1;
+	visible[ivec_to_index(from)] = true;
+	for dir in DIR_ALL {
+		let mut at = from;
+		while 0 != room.routes[ivec_to_index(at)] & (1 << dir as u8) {
+			at += DIR_COMPASS[dir as usize];
+			visible[ivec_to_index(at)] = true;
+		}
+	}
+	visible
+}
+
+// Remember what the player can see now. Call whenever the player may have moved
+pub fn room_look(room:&mut Room) {
+	let visible = room_visible(room);
+	room.seen.zip_mut_with(&visible, |seen, &visible| *seen |= visible);
 }
 
 fn _debug_room(routes: &Array2<u8>, origin:IVec2, player:IVec2, dir:usize) {
@@ -37,77 +68,133 @@ fn _debug_room(routes: &Array2<u8>, origin:IVec2, player:IVec2, dir:usize) {
 	}
 }
 
-const TILES:u32 = CANVAS_SIDE/TILE_SIDE - 1;
+pub const TILES:u32 = CANVAS_SIDE/TILE_SIDE - 1; // Default room side; the most that fits the canvas unscaled
+
+fn room_random_cell(rng:&mut impl Rng, size:IVec2) -> IVec2 {
+	IVec2::new(rng.gen_range(0..size.x), rng.gen_range(0..size.y))
+}
 
-pub fn room_make(add_actors:bool) -> Room {
-	// NDArray helpers
-	fn within (at:IVec2, size:IVec2) -> bool {
-		IVec2::ZERO.cmple(at).all() && size.cmpgt(at).all()
+// Random cell with no actor on it, if there are any left
+fn room_free_cell(rng:&mut impl Rng, size:IVec2, actors:&[(Actor, IVec2)]) -> Option<IVec2> {
+	if actors.len() >= (size.x*size.y) as usize { return None } // Actors never share a cell here
+	loop {
+		let at = room_random_cell(rng, size);
+		if !actors.iter().any(|&(_, actor_at)| actor_at == at) { return Some(at) }
 	}
+}
 
-	// Make map
-	let routes_bound = IVec2::new(TILES as i32, TILES as i32);
-	let mut routes:Array2<u8> = Array2::default(ivec_to_index(routes_bound));
-	let mut actors:Vec<(Actor, IVec2)> = Default::default();
-	let mut rng = rand::thread_rng();
-	type ObjCand = (IVec2, u32);
-	let mut path_max: [Option<ObjCand>; 4] = [None, None, None, None];
-	{
-		// Must randomize indices rather than directions because rotation identity matters
-		const COMPASS_IDX:[usize;4] = [2,1,0,3];
-		
-		let mut stack = vec![(routes_bound/2, COMPASS_IDX, 0, None::<usize>, 0)]; // See…
-		loop {
-			let top = stack.pop();
-			if top == None { break }
-			let (at, compass_order, compass_order_idx, root_branch, root_distance) = top.unwrap(); // …here
-			// (Note: root_s only needed for player_decorate case)
-
-			if compass_order_idx < 3 {
-				stack.push((at, compass_order, compass_order_idx+1, root_branch, root_distance));
-			}
+// What to put in a generated room. Keys and doors are at least 1 if actors is set.
+// Keys and doors are plain; each gate brings its own colored key
+#[derive(Clone, Copy, Debug)]
+pub struct RoomSpec {
+	pub actors:bool,
+	pub keys:u32,
+	pub doors:u32,
+	pub gates:u32,
+	pub ammo:u32,
+	pub monsters:u32,
+	pub maze:MazeStyle,
+}
+
+impl RoomSpec {
+	// Difficulty ramp: level 0 is one key, one door
+	pub fn level(level:u32) -> Self {
+		RoomSpec {
+			actors: true,
+			keys: (1 + level/3).min(KEY_SPAWN_MAX),
+			doors: (1 + level/4).min(DOOR_SPAWN_MAX),
+			gates: (level/2).min(GATE_SPAWN_MAX),
+			ammo: AMMO_SPAWN + level/2,
+			monsters: (MONSTER_SPAWN + level).min(MONSTER_SPAWN_MAX),
+			maze: MazeStyle::default(),
+		}
+	}
+}
 
-			let compass_idx = compass_order[compass_order_idx];
-			let cand = at + DIR_COMPASS[compass_idx];
+impl Default for RoomSpec {
+	fn default() -> Self { Self::level(0) }
+}
 
-			let root_branch = root_branch.unwrap_or(compass_idx);
+// Open a way into a cell from the left or right if it has none except from closed cells, so a key there
+// can be picked up. Only for the first key: once gates are down, a new passage could lead around one
+fn room_key_open(routes:&mut Array2<u8>, at:IVec2, closed:&[IVec2], rng:&mut impl Rng) {
+	let (height, width) = routes.dim();
+	let size = IVec2::new(width as i32, height as i32);
+	let sides:Vec<Dir> = [Dir::Left, Dir::Right].into_iter().filter(|&dir| {
+		let from = at + DIR_COMPASS[dir as usize];
+		within(from, size) && !closed.contains(&from)
+	}).collect();
+	if sides.iter().any(|&dir| 0 != routes[ivec_to_index(at)] & 1<<dir as u8) { return }
+	let approach = match sides[..] {
+		[] => return,
+		[only] => only,
+		_ => if rng.gen_range(0..=1) != 0 { Dir::Left } else { Dir::Right }
+	};
+	routes[ivec_to_index(at)] |= 1<<approach as u8;
+	routes[ivec_to_index(at + DIR_COMPASS[approach as usize])] |= 1<<((approach as usize+2)%4);
+}
 
-			if add_actors { // We could do this one-fourth as often, but then we'd need special behavior for the case where the root is blocked on 3 sides
-				let current_path_max = path_max[root_branch];
-				let replace;
-				if let Some((_, distance)) = current_path_max {
-					replace = distance < root_distance;
-				} else { replace = true; }
-				if replace {
-					path_max[root_branch] = Some((at, root_distance));
-				}
-			}
+// Key facing one of the ways it can be picked up (from room_key_facings), randomly if there are two.
+// None if it can't be picked up at all
+fn room_key(facings:&[bool], kind:u8, rng:&mut impl Rng) -> Option<Actor> {
+	let rightward = match facings {
+		[] => return None,
+		[only] => *only,
+		_ => rng.gen_range(0..=1) != 0
+	};
+	Some(Actor::Key(rightward, kind))
+}
 
-			if within(cand, routes_bound) {
-				let cand_value = routes[ivec_to_index(cand)];
-				let is_free = cand_value == 0;
-				if is_free {
-					let mut random_compass = COMPASS_IDX.clone();
-					random_compass.shuffle(&mut rng);
-					stack.push((cand, random_compass, 0, Some(root_branch), root_distance+1));
-				}
-//println!("\nFrom {} check {}: {}, {}", at, cand, is_free, 0 != cand_value & 1<<((compass_idx+2)%4)); _debug_room(&routes, routes_bound/2, at, compass_idx);
-				if is_free || 0 != cand_value & 1<<((compass_idx+2)%4) {
-					routes[ivec_to_index(at)] |= 1<<compass_idx; // Reciprocate
-				}
-			}
+// Which way the player has to be moving to pick up a key
+pub fn room_key_dir(rightward:bool) -> Dir {
+	if rightward { Dir::Right } else { Dir::Left }
+}
+
+// Ways a key at at could face and still be picked up. The player steps in from the cell behind it,
+// so there has to be a passage from there, and it has to be a cell they can get to (in reach, not closed)
+fn room_key_facings(routes:&Array2<u8>, at:IVec2, reach:&Array2<bool>, closed:&[IVec2]) -> Vec<bool> {
+	[false, true].into_iter().filter(|&rightward| {
+		let dir = room_key_dir(rightward) as usize;
+		let from = at - DIR_COMPASS[dir];
+		0 != routes[ivec_to_index(at)] & 1<<((dir+2)%4) && reach[ivec_to_index(from)] && !closed.contains(&from)
+	}).collect()
+}
+
+// Breadth-first search down open passages, never entering closed cells. For each cell reached,
+// gives the one before it on a shortest path (the start gives itself)
+pub(crate) fn room_search(routes:&Array2<u8>, from:IVec2, closed:&[IVec2]) -> Array2<Option<IVec2>> {
+	let mut previous:Array2<Option<IVec2>> = Array2::default(routes.dim());
+	previous[ivec_to_index(from)] = Some(from);
+	let mut queue = VecDeque::from([from]);
+	while let Some(at) = queue.pop_front() {
+		for (compass_idx, offset) in DIR_COMPASS.iter().enumerate() {
+			let next = at + *offset;
+			if 0 == routes[ivec_to_index(at)] & 1<<compass_idx || previous[ivec_to_index(next)].is_some() || closed.contains(&next) { continue }
+			previous[ivec_to_index(next)] = Some(at);
+			queue.push_back(next);
 		}
 	}
+	previous
+}
 
+// NDArray helpers
+pub(crate) fn within (at:IVec2, size:IVec2) -> bool {
+	IVec2::ZERO.cmple(at).all() && size.cmpgt(at).all()
+}
+
+// Wall tiles sit on the corners between cells, so there is one more of them on each axis
+pub fn room_walls(routes:&Array2<u8>) -> Array2<u8> {
+	let (height, width) = routes.dim();
+	let routes_bound = IVec2::new(width as i32, height as i32);
 	let walls_bound = routes_bound + IVec2::ONE;
 	let mut walls:Array2<u8> = Array2::default(ivec_to_index(walls_bound));
 	// Instead of iterating over the members of the array imagine the grid separating members of the array,
 	// and imagine iterating over the intersection points.
-	for y in 0..(TILES+1) {
-		for x in 0..(TILES+1) {
+	for y in 0..walls_bound.y {
+		for x in 0..walls_bound.x {
 			let at = (y as usize, x as usize);
-			let up_left = IVec2::new(x as i32-1,y as i32-1);
-			let down_left = IVec2::new(x as i32-1,y as i32);
+			let up_left = IVec2::new(x-1,y-1);
+			let down_left = IVec2::new(x-1,y);
 
 			if !within(up_left, routes_bound) || 0==routes[ivec_to_index(up_left)]&DirMask::Right as u8
 				{ walls[at] |= DirMask::Up as u8 }
@@ -115,11 +202,11 @@ pub fn room_make(add_actors:bool) -> Room {
 				{ walls[at] |= DirMask::Down as u8 }
 		}
 	}
-	for y in 0..(TILES+1) {
-		for x in 0..(TILES+1) {
+	for y in 0..walls_bound.y {
+		for x in 0..walls_bound.x {
 			let at = (y as usize, x as usize);
-			let up_left = IVec2::new(x as i32-1,y as i32-1);
-			let up_right = IVec2::new(x as i32,y as i32-1);
+			let up_left = IVec2::new(x-1,y-1);
+			let up_right = IVec2::new(x,y-1);
 
 			if !within(up_left, routes_bound) || 0==routes[ivec_to_index(up_left)]&DirMask::Down as u8
 				{ walls[at] |= DirMask::Left as u8 }
@@ -128,101 +215,218 @@ pub fn room_make(add_actors:bool) -> Room {
 		}
 	}
 
-	if add_actors {
-		path_max.sort_by_key(|cand| {let (_, x) = cand.unwrap(); Reverse(x) });
+	walls
+}
 
-		while path_max[2].unwrap().0 == path_max[0].unwrap().0 || path_max[2].unwrap().0 == path_max[1].unwrap().0 {
-			path_max[2] = Some((IVec2::new(rng.gen_range(0..TILES) as i32, rng.gen_range(0..TILES) as i32), 0));
+// Sanity checks for a room loaded from outside (what names it, for messages): every passage has to
+// stay in the room and be open from both sides, and there has to be exactly one player. Gives the player's cell
+pub fn room_check(what:&str, routes:&Array2<u8>, actors:&[(Actor, IVec2)]) -> Result<IVec2, String> {
+	let (height, width) = routes.dim();
+	let size = IVec2::new(width as i32, height as i32);
+	for ((y, x), &route) in routes.indexed_iter() {
+		let at = IVec2::new(x as i32, y as i32);
+		for (dir, offset) in DIR_ALL.iter().zip(DIR_COMPASS) {
+			if 0 == route & (1 << *dir as u8) { continue }
+			let next = at + offset;
+			if !within(next, size) {
+				return Err(format!("{} has a passage out of the room at {}, {}", what, at.x, at.y))
+			}
+			if 0 == routes[ivec_to_index(next)] & (1 << ((*dir as usize + 2) % 4)) {
+				return Err(format!("{} has a one-way passage at {}, {}", what, at.x, at.y))
+			}
 		}
+	}
 
-		for ord in 0..=2 {
-			let (at, _) = path_max[ord].unwrap();
-			let actor = match ord { 0 => Actor::Door, 1 => Actor::Player(if rng.gen_range(0..=1) == 0 {Dir::Left} else {Dir::Right}), _ => Actor::Key({
-				let route = routes[ivec_to_index(at)];
-				     if 0==route&DirMask::Left as u8 && 0!=route&DirMask::Right as u8 { false }
-				else if 0!=route&DirMask::Left as u8 && 0==route&DirMask::Right as u8 { true }
-				else { rng.gen_range(0..=1) != 0 }
-			})};
-
-			actors.push((actor, at));
-		}
+	let players:Vec<IVec2> = actors.iter().filter(|(actor, _)| matches!(actor, Actor::Player(_))).map(|&(_, at)| at).collect();
+	match players[..] {
+		[at] => Ok(at),
+		[] => Err(format!("{} has no player", what)),
+		_ => Err(format!("{} has {} players, but needs exactly one", what, players.len()))
 	}
+}
 
-	Room { routes, walls, actors }
+// Same spec and same rng state always give the same room, on any platform.
+// With actors, unsolvable rooms and rooms outside the difficulty band are thrown away and
+// another made (from where the rng left off); if none work out, the closest miss is used,
+// which could in the worst case be unsolvable (that gets a warning)
+pub fn room_make(spec:&RoomSpec, rng:&mut impl Rng) -> Room {
+	if !spec.actors { return room_make_once(spec, rng) }
+	let (low, high) = spec.maze.difficulty.unwrap_or((0, u32::MAX));
+
+	let mut best:Option<(u32, Room)> = None;
+	for _ in 0..ROOM_ATTEMPTS_MAX {
+		let room = room_make_once(spec, rng);
+		let miss = match solve_metrics(&room).difficulty {
+			None => u32::MAX,
+			Some(difficulty) if difficulty < low => low - difficulty,
+			Some(difficulty) if difficulty > high => difficulty - high,
+			Some(_) => return room
+		};
+		if best.as_ref().map_or(true, |(best_miss, _)| miss < *best_miss) { best = Some((miss, room)); }
+	}
+	let (miss, room) = best.unwrap();
+	if miss == u32::MAX { log::warn!("No solvable room in {} tries, so this one can't be finished", ROOM_ATTEMPTS_MAX); }
+	room
 }
 
-pub fn room_render(room: &Room, queue: &wgpu::Queue, buffer: &wgpu::Buffer, pos_scale:IVec2, tex_scale:IVec2, actor_draw:bool) -> u64 {
-	// Notice y,x order
-	const OFFSET:i32 = (CANVAS_SIDE as i32 - TILE_SIDE as i32*(TILES as i32 + 1))/2;
-	const TILE_SIZE:IVec2 = IVec2::new(TILE_SIDE as i32, TILE_SIDE as i32);
+fn room_make_once(spec:&RoomSpec, rng:&mut impl Rng) -> Room {
+	let add_actors = spec.actors;
 
-	let (pos_scale, tex_scale) = (pos_scale.as_vec2(), tex_scale.as_vec2());
-	let tex_scale_reflect = Vec2::new(-tex_scale.x, tex_scale.y);
+	// Make map
+	let routes_bound = spec.maze.size;
+	let mut routes:Array2<u8> = Array2::default(ivec_to_index(routes_bound));
+	let mut actors:Vec<(Actor, IVec2)> = Default::default();
+	maze_carve(&spec.maze, &mut routes, rng);
 
-	// Make position, make tile
-	let mp = |v:IVec2| { make_float(v, pos_scale) };
-	let mt = |v:IVec2, reflect:bool| { make_float(v, if reflect { tex_scale_reflect } else { tex_scale }) };
+	// Farthest cell down each of the four ways out of the center (the center itself if that way is closed)
+	type ObjCand = (IVec2, u32);
+	let center = routes_bound/2;
+	let mut path_max:[ObjCand;4] = [(center, 0);4];
+	if add_actors {
+		let mut reached:Array2<bool> = Array2::default(routes.dim());
+		reached[ivec_to_index(center)] = true;
+		let mut queue:VecDeque<(IVec2, usize, u32)> = VecDeque::default(); // Cell, root branch, distance
+		for (root_branch, offset) in DIR_COMPASS.iter().enumerate() {
+			if 0 != routes[ivec_to_index(center)] & 1<<root_branch {
+				reached[ivec_to_index(center + *offset)] = true;
+				queue.push_back((center + *offset, root_branch, 1));
+			}
+		}
+		while let Some((at, root_branch, root_distance)) = queue.pop_front() {
+			if path_max[root_branch].1 < root_distance {
+				path_max[root_branch] = (at, root_distance);
+			}
+			for (compass_idx, offset) in DIR_COMPASS.iter().enumerate() {
+				let cand = at + *offset;
+				if 0 != routes[ivec_to_index(at)] & 1<<compass_idx && !reached[ivec_to_index(cand)] {
+					reached[ivec_to_index(cand)] = true;
+					queue.push_back((cand, root_branch, root_distance+1));
+				}
+			}
+		}
+	}
 
-	let mut storage:Vec<u8> = Vec::default(); 
+	if add_actors {
+		path_max.sort_by_key(|&(_, x)| Reverse(x));
+
+		// The key also needs a neighbor to the left or right, other than the door, to be picked up from
+		let door_at = path_max[0].0;
+		let door_only_way_in = |at:IVec2| [Dir::Left, Dir::Right].into_iter().map(|dir| at + DIR_COMPASS[dir as usize])
+			.all(|from| !within(from, routes_bound) || from == door_at);
+		while path_max[2].0 == path_max[0].0 || path_max[2].0 == path_max[1].0 || door_only_way_in(path_max[2].0) {
+			path_max[2] = (room_random_cell(rng, routes_bound), 0);
+		}
 
-	'grid: for (y,col) in room.walls.axis_iter(Axis(0)).enumerate() {
-		for (x,&tile_which) in col.iter().enumerate() {
-			let tile_which = tile_which as u32; // Notice y,x order
-			let sprite = [
-				mp(IVec2::new((x as u32*TILE_SIDE) as i32 + OFFSET, (y as u32*TILE_SIDE) as i32 + OFFSET)),
-				mp(TILE_SIZE),
-				mt(IVec2::new(((tile_which%TILE_ROW_MAX)*TILE_SIDE) as i32, (TILE_Y_ORIGIN+(tile_which/TILE_ROW_MAX)*TILE_SIDE) as i32), false),
-				mt(TILE_SIZE, false)
-			];
+		let everywhere = Array2::from_elem(routes.dim(), true); // Before any gates, the whole maze is in reach
+		for ord in 0..=2 {
+			let (at, _) = path_max[ord];
+			let actor = match ord { 0 => Actor::Door, 1 => Actor::Player(if rng.gen_range(0..=1) == 0 {Dir::Left} else {Dir::Right}), _ => {
+				// Not picked up from the door, which can't be stood on without this key
+				room_key_open(&mut routes, at, &[door_at], rng);
+				room_key(&room_key_facings(&routes, at, &everywhere, &[door_at]), 0, rng).expect("room_key_open made a way in")
+			}};
 
-//			assert!(mem::size_of_val(&sprite) as u64 == SPRITE_SIZE);
+			actors.push((actor, at));
+		}
 
-			if storage.len() as u64 + SPRITE_SIZE > buffer.size() as u64 { break 'grid }
+		// Gates go across corridors on the way from player to door, in order, and only where shutting them
+		// really cuts the door off (braided mazes have ways around). Each gate is beyond what the one before
+		// closes off, so it can't cut off an earlier key. Each key goes where it can be picked up with only
+		// the gates before its own open: reaching its cell isn't enough, the player also has to be able to
+		// step in from behind, which mustn't be the gate or past it (and past the last gate, if there's space)
+		let player_at = path_max[1].0;
+		let free = |at:IVec2, actors:&[(Actor, IVec2)]| !actors.iter().any(|&(_, actor_at)| actor_at == at);
+		let mut behind_last:Array2<bool> = Array2::default(routes.dim()); // Reachable with the latest gate shut
+		for gate in 0..spec.gates {
+			let kind = (1 + gate % (KEY_KINDS-1)) as u8;
+			let to_door = room_search(&routes, player_at, &[]);
+			let mut path = vec![];
+			let mut at = door_at;
+			while at != player_at { path.push(at); at = to_door[ivec_to_index(at)].unwrap(); }
+			let corridor:Vec<IVec2> = path.into_iter().rev()
+				.filter(|&at| routes[ivec_to_index(at)].count_ones() == 2 && !behind_last[ivec_to_index(at)] && free(at, &actors))
+				.filter(|&at| room_search(&routes, player_at, &[at])[ivec_to_index(door_at)].is_none()).collect();
+			let Some(&gate_at) = corridor.choose(rng) else { break };
+
+			let reach = room_search(&routes, player_at, &[gate_at]).map(|previous| previous.is_some());
+			let keys:Vec<(IVec2, bool)> = reach.indexed_iter().filter(|&(_, &reached)| reached)
+				.map(|((y, x), _)| IVec2::new(x as i32, y as i32)).filter(|&at| free(at, &actors))
+				.flat_map(|at| room_key_facings(&routes, at, &reach, &[gate_at]).into_iter().map(move |rightward| (at, rightward))).collect();
+			let past_last:Vec<(IVec2, bool)> = keys.iter().copied().filter(|&(at, _)| !behind_last[ivec_to_index(at)]).collect();
+			let Some(&(key_at, rightward)) = [&past_last, &keys].into_iter().find(|keys| !keys.is_empty()).and_then(|keys| keys.choose(rng)) else { break };
+
+			actors.push((Actor::Gate(kind), gate_at));
+			actors.push((Actor::Key(rightward, kind), key_at));
+			behind_last = reach;
+		}
 
-			let bytes = bytemuck::bytes_of(&sprite);
+		// Any further keys go anywhere they can be picked up. In a small room, whatever doesn't fit is left out
+		for _ in 1..spec.keys {
+			let keys:Vec<(IVec2, Vec<bool>)> = routes.indexed_iter().map(|((y, x), _)| IVec2::new(x as i32, y as i32))
+				.map(|at| (at, room_key_facings(&routes, at, &everywhere, &[])))
+				.filter(|(at, facings)| !facings.is_empty() && free(*at, &actors)).collect();
+			let Some((at, facings)) = keys.choose(rng) else { break };
+			actors.push((room_key(facings, 0, rng).unwrap(), *at));
+		}
+		// Further doors only go in dead ends, where they can't cut anything off. Not beside a key, though,
+		// since that could be the only side it can be picked up from
+		for _ in 1..spec.doors {
+			let beside_key = |at:IVec2| actors.iter().any(|&(actor, actor_at)| {
+				let offset = (actor_at - at).abs();
+				matches!(actor, Actor::Key(..)) && offset.x + offset.y == 1
+			});
+			let dead_ends:Vec<IVec2> = routes.indexed_iter()
+				.filter(|&(_, &route)| route.count_ones() == 1)
+				.map(|((y, x), _)| IVec2::new(x as i32, y as i32))
+				.filter(|&at| free(at, &actors) && !beside_key(at)).collect();
+			let Some(&at) = dead_ends.choose(rng) else { break };
+			actors.push((Actor::Door, at));
+		}
 
-			storage.extend_from_slice(bytes);
+		for _ in 0..spec.ammo {
+			let Some(at) = room_free_cell(rng, routes_bound, &actors) else { break };
+			actors.push((Actor::Ammo, at));
 		}
-	}
 
-	if actor_draw {
-		const ACTOR_SIZE:IVec2 = IVec2::new(ACTOR_SIDE as i32, ACTOR_SIDE as i32);
-
-		'sprite: for actor in &room.actors {
-			let (actor, at) = actor;
-			let (actor_which, reflect) = match actor {
-				Actor::Player(Dir::Right) => (0, false),
-				Actor::Player(Dir::Left) => (0, true),
-				Actor::Player(Dir::Down) => (1, false),
-				Actor::Player(Dir::Up) => (2, false),
-				Actor::Door => (3, false),
-				Actor::Key(true) => (4, false),
-				Actor::Key(false) => (5, false),
-				Actor::Shot => (6, false),
-				Actor::Ammo => (7, false),
-				Actor::Monster(_, n) => (8+*n as u32, false), // FIXME: Must assert MONSTER_Y_ORIGIN == 0, MONSTER_X_ORIGIN == 8*8
+		let far = |at:IVec2| { let offset = (at - player_at).abs(); offset.x + offset.y >= MONSTER_SPAWN_DISTANCE };
+		for _ in 0..spec.monsters {
+			// Don't start too close to the player
+			let room_left = (0..routes_bound.y).any(|y| (0..routes_bound.x).any(|x| {
+				let at = IVec2::new(x, y);
+				far(at) && !actors.iter().any(|&(_, actor_at)| actor_at == at)
+			}));
+			if !room_left { break }
+			let at = loop {
+				let at = room_free_cell(rng, routes_bound, &actors).unwrap();
+				if far(at) { break at }
 			};
-			let sprite = [
-				mp(IVec2::new(at.x*TILE_SIDE as i32 + OFFSET + 6, at.y*TILE_SIDE as i32 + OFFSET + 6)),
-				mp(ACTOR_SIZE),
-				mt(IVec2::new(((actor_which + if reflect { 1 } else { 0 })*ACTOR_SIDE) as i32, ACTOR_Y_ORIGIN as i32), false),
-				mt(ACTOR_SIZE, reflect)
-			];
-
-//			assert!(mem::size_of_val(&sprite) as u64 == SPRITE_SIZE);
-
-			if storage.len() as u64 + SPRITE_SIZE > buffer.size() as u64 { break 'sprite }
-
-			let bytes = bytemuck::bytes_of(&sprite);
-
-			storage.extend_from_slice(bytes);
+			let dir = DIR_ALL[rng.gen_range(0..4u32) as usize];
+			actors.push((Actor::Monster(dir, rng.gen_range(0..MONSTER_COUNT) as u8), at));
 		}
 	}
 
-	let len = storage.len() as u64;
-//	println!("FLOOR LEN {} ({})", len, len / SPRITE_SIZE);
-
-	queue.write_buffer(&buffer, 0, &storage);
+	let walls = room_walls(&routes); // The first key may have opened a passage
+	let seen = Array2::default(routes.dim());
+	let mut room = Room { routes, walls, actors:actors.into_iter().collect(), seen };
+	room_look(&mut room);
+	room
+}
 
-	len / SPRITE_SIZE // byte count
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::game::*;
+	use crate::level::*;
+
+	// Gated rooms have to come out solvable on the first try; room_make's retries are only a backstop
+	#[test]
+	fn room_make_once_solvable() {
+		for seed in 0..1500u64 {
+			let (kind, _) = MAZE_KINDS[seed as usize % MAZE_KINDS.len()];
+			let level = 2 + seed as u32 % 12;
+			let spec = RoomSpec { maze:MazeStyle { kind, braid:(seed as u32 % 3)*30, ..MazeStyle::default() }, ..RoomSpec::level(level) };
+			let room = room_make_once(&spec, &mut game_rng(seed, level as u64));
+			assert!(solve_route(&room).is_some(), "seed {} level {} can't be solved:\n{}", seed, level, level_write(&room));
+		}
+	}
 }
@@ -0,0 +1,223 @@
+// Saved games: GameState and Room as versioned text, kept in a file on desktop or localStorage on web.
+//
+//   zap-save 4
+//   seed 1234
+//   level 2
+//   keys 1 0 2 0
+//   ammo 3
+//   score 60
+//   dead 0
+//   rng 5678
+//   maze backtracker 0 11 11
+//   room 12 12
+//   <one line per row of routes, one hex digit (DirMask bits) per cell>
+//   <one line per row of seen cells, 1 if seen and 0 if not>
+//   actor 5 5 player R
+//   actor 9 8 door
+//   actor 7 6 key 1 0
+//   actor 7 8 gate 2
+//   actor 3 4 shot D
+//   actor 9 6 monster L 5
+//
+// Walls are not stored, since room_walls rebuilds them exactly from the routes.
+// Actors are renumbered on load, so handles from before a save are not valid after it.
+
+use crate::constants::*;
+use crate::game::*;
+use crate::maze::*;
+use crate::room::*;
+
+use glam::IVec2;
+use ndarray::{Array2, Axis};
+
+const SAVE_HEADER:&str = "zap-save 4";
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_FILE:&str = "zap_save.txt";
+#[cfg(target_arch = "wasm32")]
+const SAVE_KEY:&str = "zap-save";
+
+pub fn save_write(state:&GameState, room:&Room) -> String {
+	let (height, width) = room.routes.dim();
+	let mut out = format!("{}\nseed {}\nlevel {}\nkeys {}\nammo {}\nscore {}\ndead {}\nrng {}\nmaze {}\nroom {} {}\n",
+		SAVE_HEADER, state.seed, state.level, state.keys.map(|count| count.to_string()).join(" "), state.ammo, state.score, state.dead as u8,
+		state.rng.get_word_pos(), maze_style_write(&state.maze), width, height);
+
+	for row in room.routes.axis_iter(Axis(0)) {
+		out.extend(row.iter().map(|&route| char::from_digit(route as u32, 16).unwrap()));
+		out.push('\n');
+	}
+	for row in room.seen.axis_iter(Axis(0)) {
+		out.extend(row.iter().map(|&seen| if seen { '1' } else { '0' }));
+		out.push('\n');
+	}
+
+	for (_, (actor, at)) in room.actors.iter() {
+		let actor = match *actor {
+			Actor::Player(dir) => format!("player {}", DIR_LETTER[dir as usize]),
+			Actor::Door => "door".to_string(),
+			Actor::Key(rightward, kind) => format!("key {} {}", rightward as u8, kind),
+			Actor::Gate(kind) => format!("gate {}", kind),
+			Actor::Ammo => "ammo".to_string(),
+			Actor::Shot(dir) => format!("shot {}", DIR_LETTER[dir as usize]),
+			Actor::Monster(dir, sprite) => format!("monster {} {}", DIR_LETTER[dir as usize], sprite),
+		};
+		out.push_str(&format!("actor {} {} {}\n", at.x, at.y, actor));
+	}
+
+	out
+}
+
+pub fn save_read(text:&str) -> Result<(GameState, Room), String> {
+	let mut lines = text.lines().enumerate();
+
+	// Each header line is "name value..."
+	let mut field = |name:&str| -> Result<(usize, Vec<String>), String> {
+		let (idx, line) = lines.next().ok_or_else(|| format!("Save ends before \"{}\"", name))?;
+		let mut words = line.split_whitespace();
+		if words.next() != Some(name) { return Err(format!("Line {}: expected \"{} ...\"", idx+1, name)) }
+		Ok((idx+1, words.map(String::from).collect()))
+	};
+	fn number<T:std::str::FromStr>(line:usize, words:&[String], which:usize) -> Result<T, String> {
+		words.get(which).and_then(|word| word.parse().ok())
+			.ok_or_else(|| format!("Line {}: expected a number", line))
+	}
+	fn one<T:std::str::FromStr>((line, words):(usize, Vec<String>)) -> Result<T, String> {
+		number(line, &words, 0)
+	}
+
+	let (_, version) = field("zap-save")?;
+	if format!("zap-save {}", version.join(" ")) != SAVE_HEADER { return Err(format!("Unsupported save version {}", version.join(" "))) }
+
+	let mut state = GameState::new(one(field("seed")?)?);
+	state.level = one(field("level")?)?;
+	let (line, words) = field("keys")?;
+	if words.len() != KEY_KINDS as usize { return Err(format!("Line {}: expected {} key counts", line, KEY_KINDS)) }
+	for (kind, count) in state.keys.iter_mut().enumerate() { *count = number(line, &words, kind)?; }
+	state.ammo = one(field("ammo")?)?;
+	state.score = one(field("score")?)?;
+	state.dead = one::<u8>(field("dead")?)? != 0;
+	state.rng.set_word_pos(one(field("rng")?)?);
+	let (line, words) = field("maze")?;
+	state.maze = maze_style_read(&words.join(" ")).ok_or_else(|| format!("Line {}: bad maze", line))?;
+
+	let (line, words) = field("room")?;
+	let (width, height):(usize, usize) = (number(line, &words, 0)?, number(line, &words, 1)?);
+
+	let mut routes:Array2<u8> = Array2::default((height, width));
+	for y in 0..height {
+		let (idx, line) = lines.next().ok_or("Save ends inside room")?;
+		let row:Vec<u8> = line.chars().map(|c| c.to_digit(16).map(|route| route as u8)).collect::<Option<_>>()
+			.ok_or_else(|| format!("Line {}: routes must be hex digits", idx+1))?;
+		if row.len() != width { return Err(format!("Line {}: expected {} cells, found {}", idx+1, width, row.len())) }
+		for (x, route) in row.into_iter().enumerate() { routes[(y, x)] = route; }
+	}
+
+	let mut seen:Array2<bool> = Array2::default((height, width));
+	for y in 0..height {
+		let (idx, line) = lines.next().ok_or("Save ends inside room")?;
+		let row:Vec<bool> = line.chars().map(|c| match c { '0' => Some(false), '1' => Some(true), _ => None }).collect::<Option<_>>()
+			.ok_or_else(|| format!("Line {}: seen cells must be 0 or 1", idx+1))?;
+		if row.len() != width { return Err(format!("Line {}: expected {} cells, found {}", idx+1, width, row.len())) }
+		for (x, seen_cell) in row.into_iter().enumerate() { seen[(y, x)] = seen_cell; }
+	}
+
+	let mut actors:Vec<(Actor, IVec2)> = Vec::default();
+	for (idx, line) in lines {
+		let words:Vec<&str> = line.split_whitespace().collect();
+		let bad = || format!("Line {}: bad actor \"{}\"", idx+1, line);
+		let dir = |which:usize| words.get(which).and_then(|word| word.chars().next()).and_then(letter_to_dir).ok_or_else(bad);
+		let number = |which:usize| words.get(which).and_then(|word| word.parse::<i32>().ok()).ok_or_else(bad);
+		let ranged = |which:usize, range:std::ops::Range<u32>| number(which).and_then(|value|
+			if value >= 0 && range.contains(&(value as u32)) { Ok(value as u8) } else { Err(format!("Line {}: {} is out of range", idx+1, value)) });
+
+		if words.is_empty() { continue }
+		if words[0] != "actor" { return Err(bad()) }
+		let at = IVec2::new(number(1)?, number(2)?);
+		if at.x < 0 || at.y < 0 || at.x as usize >= width || at.y as usize >= height {
+			return Err(format!("Line {}: actor outside room", idx+1))
+		}
+		let actor = match words.get(3).copied() {
+			Some("player") => Actor::Player(dir(4)?),
+			Some("door") => Actor::Door,
+			Some("key") => Actor::Key(number(4)? != 0, ranged(5, 0..KEY_KINDS)?),
+			Some("gate") => Actor::Gate(ranged(4, 1..KEY_KINDS)?), // Kind 0 keys open doors, not gates
+			Some("ammo") => Actor::Ammo,
+			Some("shot") => Actor::Shot(dir(4)?),
+			Some("monster") => Actor::Monster(dir(4)?, ranged(5, 0..MONSTER_COUNT)?),
+			_ => return Err(bad())
+		};
+		actors.push((actor, at));
+	}
+	room_check("Save", &routes, &actors)?;
+
+	let walls = room_walls(&routes);
+	let room = Room { routes, walls, actors:actors.into_iter().collect(), seen };
+	state.player = room_player(&room).unwrap(); // room_check guarantees one
+	Ok((state, room))
+}
+
+// Persist a save_write string. Desktop: file in the working directory; web: localStorage
+pub fn save_store(text:&str) -> Result<(), String> {
+	#[cfg(not(target_arch = "wasm32"))]
+	{
+		std::fs::write(SAVE_FILE, text).map_err(|err| format!("Couldn't write {}: {}", SAVE_FILE, err))
+	}
+	#[cfg(target_arch = "wasm32")]
+	{
+		web_sys::window().and_then(|window| window.local_storage().ok().flatten())
+			.ok_or("No localStorage")?
+			.set_item(SAVE_KEY, text).map_err(|_| "Couldn't write localStorage".to_string())
+	}
+}
+
+// Whatever save_store last stored, if anything
+pub fn save_fetch() -> Option<String> {
+	#[cfg(not(target_arch = "wasm32"))]
+	{
+		std::fs::read_to_string(SAVE_FILE).ok()
+	}
+	#[cfg(target_arch = "wasm32")]
+	{
+		web_sys::window()?.local_storage().ok()??.get_item(SAVE_KEY).ok()?
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::session::*;
+
+	#[test]
+	fn save_round_trip() {
+		let mut session = session_make(1234, MazeStyle::default(), 0, false);
+		for dir in [Dir::Right, Dir::Down, Dir::Left, Dir::Up, Dir::Down] { session_input(&mut session, Input::Move(dir)); }
+		let text = save_write(&session.state, &session.room);
+		let (state, room) = save_read(&text).unwrap();
+		assert_eq!(save_write(&state, &room), text);
+		assert_eq!(state.rng, session.state.rng);
+		assert_eq!(room.walls, session.room.walls);
+		assert_eq!(room.actors[state.player], session.room.actors[session.state.player]);
+	}
+
+	// Two cells side by side, with the given routes and actor lines
+	fn save_tiny(routes:&str, actors:&str) -> String {
+		format!("{}\nseed 1\nlevel 0\nkeys {}\nammo 0\nscore 0\ndead 0\nrng 0\nmaze {}\nroom 2 1\n{}\n00\n{}",
+			SAVE_HEADER, vec!["0"; KEY_KINDS as usize].join(" "), maze_style_write(&MazeStyle::default()), routes, actors)
+	}
+
+	#[test]
+	fn save_read_rejects_broken_rooms() {
+		let actors = "actor 0 0 player R\nactor 1 0 door\n";
+		assert!(save_read(&save_tiny("14", actors)).is_ok());
+		assert!(save_read(&save_tiny("10", actors)).is_err()); // Right from the first cell, but not back
+		assert!(save_read(&save_tiny("94", actors)).is_err()); // Up out of the room
+		assert!(save_read(&save_tiny("14", "actor 1 0 door\n")).is_err());
+		assert!(save_read(&save_tiny("14", "actor 0 0 player R\nactor 1 0 player L\n")).is_err());
+
+		// Kinds past the last color, a gate for plain keys, and a monster with no sprite
+		for actor in ["key 1 4", "key 0 -1", "gate 0", "gate 4", "monster L 8"] {
+			assert!(save_read(&save_tiny("14", &format!("{}actor 1 0 {}\n", actors, actor))).is_err(), "{}", actor);
+		}
+		assert!(save_read(&save_tiny("14", &format!("{}actor 1 0 key 1 3\nactor 1 0 gate 3\nactor 1 0 monster L 7\n", actors))).is_ok());
+	}
+}
@@ -0,0 +1,84 @@
+// Everything one play session needs, driven by a stream of inputs.
+// Keyboard, replay playback and tools all go through session_input, so they behave identically
+
+use crate::constants::*;
+use crate::game::*;
+use crate::history::*;
+use crate::maze::*;
+use crate::replay::*;
+use crate::room::*;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Input {
+	Move(Dir),
+	Fire,
+	Wait, // Realtime mode's clock ticked with no other input
+	Undo,
+	Redo,
+	Reset(u64), // New game with this seed, same maze style
+}
+
+pub struct Session {
+	pub state: GameState,
+	pub room: Room,
+	pub history: History,
+	pub record: Option<Replay>, // If present, inputs are appended
+}
+
+pub fn session_make(seed:u64, maze:MazeStyle, history_depth:usize, record:bool) -> Session {
+	let mut state = GameState::new(seed);
+	state.maze = maze;
+	let room = game_room(&mut state);
+	let record = record.then(|| Replay { seed, maze, history_depth, inputs:Vec::default() });
+	Session { state, room, history:History::new(history_depth), record }
+}
+
+// Returns true if anything changed
+pub fn session_input(session:&mut Session, input:Input) -> bool {
+	let Session { state, room, history, .. } = session;
+	let changed = match input {
+		Input::Move(dir) => history.act(state, room, |state, room| game_move(state, room, dir)),
+		Input::Fire => history.act(state, room, game_fire),
+		Input::Wait => { // The clock's turn, not the player's, so not an undo step
+			let waited = game_wait(state, room);
+			if waited { history.branch(); }
+			waited
+		},
+		Input::Undo => history.undo(state, room),
+		Input::Redo => history.redo(state, room),
+		Input::Reset(seed) => {
+			let maze = state.maze;
+			*state = GameState::new(seed);
+			state.maze = maze;
+			*room = game_room(state);
+			history.clear();
+			true
+		}
+	};
+
+	if changed {
+		if let Some(record) = session.record.as_mut() { record.inputs.push(input); }
+	}
+	changed
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::level::*;
+
+	// Redo after a tick would bring back a room from before the tick, undoing it without a trace
+	#[test]
+	fn session_wait_drops_redo() {
+		let mut session = session_make(1234, MazeStyle::default(), 8, false);
+		let moved = DIR_ALL.iter().any(|&dir| session_input(&mut session, Input::Move(dir)));
+		assert!(moved, "Player can't move anywhere");
+		assert!(session_input(&mut session, Input::Undo));
+		assert!(session_input(&mut session, Input::Wait));
+
+		let before = (session.state.clone(), level_write(&session.room));
+		assert!(!session_input(&mut session, Input::Redo));
+		assert_eq!(level_write(&session.room), before.1);
+		assert_eq!(session.state, before.0);
+	}
+}
@@ -4,11 +4,15 @@ struct Sprite {
     @location(1) pos_basis: vec2<f32>,
     @location(2) pos_size: vec2<f32>,
     @location(3) tex_basis: vec2<f32>,
-    @location(4) tex_size: vec2<f32>
+    @location(4) tex_size: vec2<f32>,
+    @location(5) brightness: f32, // 1 for normal, toward 0 fades into the paper
+    @location(6) tint: u32 // Which of the palette's tints the ink takes
 };
 
 struct VertexOutput {
     @location(0) tex_coord: vec2<f32>,
+    @location(1) brightness: f32,
+    @location(2) @interpolate(flat) tint: u32,
     @builtin(position) position: vec4<f32>,
 };
 
@@ -16,9 +20,14 @@ struct VertexOutput {
 @binding(0)
 var gray: texture_2d<f32>;
 
-//@group(0)
-//@binding(1)
-//var mod_wheel: f32;
+struct Palette {
+    paper: vec4<f32>,
+    tints: array<vec4<f32>, 10>, // TINT_COUNT in palette.rs
+};
+
+@group(0)
+@binding(1)
+var<uniform> palette: Palette;
 
 // Quad positioning
 @vertex
@@ -28,30 +37,50 @@ fn vs_quad(
 ) -> VertexOutput {
     var result: VertexOutput;
     result.tex_coord = instance.tex_basis + position*instance.tex_size;
+    result.brightness = instance.brightness;
+    result.tint = instance.tint;
     let instance_postition = instance.pos_basis + position*instance.pos_size;
     let full_position = instance_postition*2. - vec2(1.,1.);
     result.position = vec4(full_position.x, -full_position.y, 0., 1.);
     return result;
 }
 
-// Draw quad unaltered (for debug?)
+// Gray level under this fragment: 0 is ink, 1 is paper (assets are alpha extracted then negated,
+// so paper is where the original was transparent)
+fn quad_gray(vertex: VertexOutput) -> f32 {
+    let dim = textureDimensions(gray);
+    return textureLoad(gray, vec2<u32>(vertex.tex_coord*vec2<f32>(dim)), 0).r;
+}
+
+// Palette color for a gray level: white is paper, black is the tint, then fade toward paper
+fn quad_color(vertex: VertexOutput, v: f32) -> vec3<f32> {
+    let ink = palette.tints[vertex.tint].rgb;
+    let color = mix(ink, palette.paper.rgb, v);
+    return mix(palette.paper.rgb, color, vertex.brightness);
+}
+
+// Draw quad opaque, paper and all
 @fragment
 fn fs_quad_direct(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let dim = textureDimensions(gray);
-    let tex = textureLoad(gray, vec2<u32>(vertex.tex_coord*vec2<f32>(dim)), 0);
-    let v = f32(tex.x); //  / 255.0
-    return vec4<f32>(tex.rrr, 1.0);
+    return vec4<f32>(quad_color(vertex, quad_gray(vertex)), 1.0);
 }
-/*
-// Draw quad unaltered (for debug?)
+
+// Draw only the ink, so whatever's underneath shows through the paper
 @fragment
 fn fs_quad_threshold(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let dim = textureDimensions(gray);
-    let tex = textureLoad(gray, vec2<u32>(vertex.tex_coord*vec2<f32>(dim)), 0);
-    let v = f32(tex.x); //  / 255.0
-    return vec4<f32>(tex.rrr, 1.0);
+    let v = quad_gray(vertex);
+    if v > 0.5 {
+        discard;
+    }
+    return vec4<f32>(quad_color(vertex, v), 1.0);
 }
-*/
+
+// Draw ink with gray as coverage, for use with alpha blending; soft edges stay soft
+@fragment
+fn fs_quad_blend(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(quad_color(vertex, 0.0), 1.0 - quad_gray(vertex));
+}
+
 // Draw quad dark green
 @fragment
 fn fs_debug(vertex: VertexOutput) -> @location(0) vec4<f32> {
@@ -0,0 +1,141 @@
+// Generational slot storage. A Handle keeps pointing at the same value no matter what
+// else is inserted or removed, and once its value is removed the handle goes stale
+// (lookups give None) rather than silently pointing at whatever reuses the slot.
+
+use std::ops::{Index, IndexMut};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Handle {
+	idx: u32,
+	generation: u32,
+}
+
+impl Handle {
+	pub const NONE:Handle = Handle { idx:u32::MAX, generation:0 }; // Never valid
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slots<T> {
+	entries: Vec<(u32, Option<T>)>, // Generation, value
+	free: Vec<u32>, // Reused last-in first-out, so insertion order is deterministic
+}
+
+impl<T> Default for Slots<T> {
+	fn default() -> Self { Self { entries: Vec::default(), free: Vec::default() } }
+}
+
+impl<T> Slots<T> {
+	pub fn insert(&mut self, value:T) -> Handle {
+		if let Some(idx) = self.free.pop() {
+			let entry = &mut self.entries[idx as usize];
+			entry.1 = Some(value);
+			Handle { idx, generation: entry.0 }
+		} else {
+			self.entries.push((0, Some(value)));
+			Handle { idx: self.entries.len() as u32 - 1, generation: 0 }
+		}
+	}
+
+	pub fn remove(&mut self, handle:Handle) -> Option<T> {
+		let entry = self.entries.get_mut(handle.idx as usize)?;
+		if entry.0 != handle.generation { return None }
+		let value = entry.1.take()?;
+		entry.0 = entry.0.wrapping_add(1);
+		self.free.push(handle.idx);
+		Some(value)
+	}
+
+	pub fn get(&self, handle:Handle) -> Option<&T> {
+		match self.entries.get(handle.idx as usize) {
+			Some((generation, value)) if *generation == handle.generation => value.as_ref(),
+			_ => None
+		}
+	}
+
+	pub fn get_mut(&mut self, handle:Handle) -> Option<&mut T> {
+		match self.entries.get_mut(handle.idx as usize) {
+			Some((generation, value)) if *generation == handle.generation => value.as_mut(),
+			_ => None
+		}
+	}
+
+	pub fn contains(&self, handle:Handle) -> bool { self.get(handle).is_some() }
+
+	pub fn len(&self) -> usize { self.entries.len() - self.free.len() }
+
+	pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+	// In slot order
+	pub fn iter(&self) -> impl Iterator<Item=(Handle, &T)> {
+		self.entries.iter().enumerate().filter_map(|(idx, (generation, value))|
+			value.as_ref().map(|value| (Handle { idx:idx as u32, generation:*generation }, value)))
+	}
+
+	// Snapshot of live handles, for loops that insert or remove as they go
+	pub fn handles(&self) -> Vec<Handle> {
+		self.iter().map(|(handle, _)| handle).collect()
+	}
+}
+
+impl<T> FromIterator<T> for Slots<T> {
+	fn from_iter<I: IntoIterator<Item=T>>(iter:I) -> Self {
+		let mut slots = Self::default();
+		for value in iter { slots.insert(value); }
+		slots
+	}
+}
+
+impl<T> Index<Handle> for Slots<T> {
+	type Output = T;
+	fn index(&self, handle:Handle) -> &T { self.get(handle).expect("Stale handle") }
+}
+
+impl<T> IndexMut<Handle> for Slots<T> {
+	fn index_mut(&mut self, handle:Handle) -> &mut T { self.get_mut(handle).expect("Stale handle") }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slots_stale_handle() {
+		let mut slots:Slots<char> = "abc".chars().collect();
+		let handles = slots.handles();
+		assert_eq!(slots.remove(handles[1]), Some('b'));
+		assert_eq!(slots.get(handles[1]), None);
+		assert!(!slots.contains(handles[1]));
+		assert_eq!(slots.remove(handles[1]), None); // Twice does nothing
+		assert_eq!(slots.len(), 2);
+		assert_eq!((slots[handles[0]], slots[handles[2]]), ('a', 'c'));
+		assert_eq!(slots.get(Handle::NONE), None);
+	}
+
+	// A reused slot gets a new generation, so the old handle still doesn't see it
+	#[test]
+	fn slots_reuse() {
+		let mut slots:Slots<char> = "abc".chars().collect();
+		let old = slots.handles()[1];
+		slots.remove(old);
+		let new = slots.insert('d');
+		assert_ne!(new, old);
+		assert_eq!(slots.get(old), None);
+		assert_eq!(slots.remove(old), None);
+		assert_eq!(slots[new], 'd');
+		assert_eq!(slots.len(), 3);
+	}
+
+	#[test]
+	fn slots_iter_skips_free() {
+		let mut slots:Slots<char> = "abcd".chars().collect();
+		let handles = slots.handles();
+		slots.remove(handles[0]);
+		slots.remove(handles[2]);
+		assert_eq!(slots.iter().map(|(_, &value)| value).collect::<String>(), "bd");
+		assert_eq!(slots.handles(), vec![handles[1], handles[3]]);
+		slots.remove(handles[1]);
+		slots.remove(handles[3]);
+		assert!(slots.is_empty());
+		assert_eq!(slots.iter().count(), 0);
+	}
+}
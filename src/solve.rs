@@ -0,0 +1,156 @@
+// Room analysis: the quickest way out, and how twisty the maze is. Used to reject
+// unsolvable rooms and to aim generation at a difficulty band.
+// Monsters and ammo are ignored, and the player starts with no keys. Keys are only picked up moving the way they face
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::constants::*;
+use crate::room::*;
+
+use glam::IVec2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RoomMetrics {
+	pub solution:Option<u32>, // Fewest inputs to get a plain key and go through a door (opening gates as needed), counting turns
+	pub backtracking:u32, // Steps of that solution onto cells it already crossed
+	pub difficulty:Option<u32>, // Solution, plus backtracking again since dead ends are where players get lost
+	pub dead_ends:u32,
+	pub junctions:u32, // Cells with three or four ways out
+	pub branch_factor:f32, // Average ways onward from a cell that isn't a dead end
+}
+
+// Where the player stands after each input of a shortest solution, starting cell first
+pub fn solve_route(room:&Room) -> Option<Vec<IVec2>> {
+	let player = room_player(room)?;
+	let (Actor::Player(facing), start) = room.actors[player] else { return None };
+
+	// Keys and gates each get a bit, set once the key is picked up or the gate opened
+	let items:Vec<(Actor, IVec2)> = room.actors.iter().map(|(_, &item)| item)
+		.filter(|(actor, _)| matches!(actor, Actor::Key(..) | Actor::Gate(_))).collect();
+	if items.len() > 64 { return None }
+	let held = |used:u64, kind:u8| items.iter().enumerate().filter(|&(bit, _)| 0 != used & 1<<bit)
+		.map(|(_, item)| match item {
+			(Actor::Key(_, key_kind), _) if *key_kind == kind => 1,
+			(Actor::Gate(gate_kind), _) if *gate_kind == kind => -1,
+			_ => 0
+		}).sum::<i32>();
+
+	// Search over position, facing, and items used. Each node is a state plus the node it came from
+	let mut nodes:Vec<(IVec2, Dir, u64, usize)> = vec![(start, facing, 0, usize::MAX)];
+	let mut reached:HashSet<(IVec2, usize, u64)> = HashSet::from([(start, facing as usize, 0)]);
+	let mut queue = VecDeque::from([0]);
+	let mut exit = None;
+	'search: while let Some(node) = queue.pop_front() {
+		let (at, dir, used, _) = nodes[node];
+		'search_dir: for want_dir in DIR_ALL {
+			let (next, mut next_used) = (at + DIR_COMPASS[want_dir as usize], used);
+			let next = if want_dir != dir {
+				at // Turning takes an input
+			} else {
+				if 0 == room.routes[ivec_to_index(at)] & (1 << dir as u8) { continue }
+				if room_actors_at(room, next).any(|(_, actor)| *actor == Actor::Door) {
+					if held(used, 0) > 0 { exit = Some((next, node)); break 'search }
+					continue
+				}
+				for (bit, (actor, item_at)) in items.iter().enumerate() {
+					if *item_at != next || 0 != used & 1<<bit { continue }
+					match actor {
+						Actor::Gate(kind) if held(used, *kind) <= 0 => continue 'search_dir,
+						Actor::Key(rightward, _) if want_dir != room_key_dir(*rightward) => (), // Passes over
+						_ => next_used |= 1<<bit
+					}
+				}
+				next
+			};
+			if !reached.insert((next, want_dir as usize, next_used)) { continue }
+			nodes.push((next, want_dir, next_used, node));
+			queue.push_back(nodes.len()-1);
+		}
+	}
+
+	let (door_at, mut node) = exit?;
+	let mut route = vec![door_at];
+	while node != usize::MAX {
+		route.push(nodes[node].0);
+		node = nodes[node].3;
+	}
+	route.reverse();
+	Some(route)
+}
+
+pub fn solve_metrics(room:&Room) -> RoomMetrics {
+	let mut metrics = RoomMetrics::default();
+
+	if let Some(route) = solve_route(room) {
+		let mut crossed:Vec<IVec2> = vec![route[0]];
+		for step in route.windows(2) {
+			if step[0] == step[1] { continue } // Turn
+			if crossed.contains(&step[1]) { metrics.backtracking += 1 } else { crossed.push(step[1]) }
+		}
+		let solution = route.len() as u32 - 1;
+		metrics.solution = Some(solution);
+		metrics.difficulty = Some(solution + metrics.backtracking);
+	}
+
+	let mut onward = 0;
+	let mut passages = 0;
+	for &route in room.routes.iter() {
+		match route.count_ones() {
+			1 => metrics.dead_ends += 1,
+			2 => (),
+			0 => continue,
+			_ => metrics.junctions += 1
+		}
+		if route.count_ones() > 1 {
+			onward += route.count_ones() - 1;
+			passages += 1;
+		}
+	}
+	if passages > 0 { metrics.branch_factor = onward as f32/passages as f32; }
+
+	metrics
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::level::*;
+
+	// One row of cells, with walls all round
+	fn solve_row(cells:&str) -> Room {
+		let border = "+-".repeat((cells.len()+1)/2) + "+";
+		level_read(&format!("{}\n|{}|\n{}\n", border, cells, border)).unwrap()
+	}
+
+	#[test]
+	fn solve_key_then_door() {
+		let room = solve_row("@ K D");
+		assert_eq!(solve_route(&room), Some(vec![IVec2::new(0, 0), IVec2::new(1, 0), IVec2::new(2, 0)]));
+		let metrics = solve_metrics(&room);
+		assert_eq!((metrics.solution, metrics.backtracking, metrics.difficulty), (Some(2), 0, Some(2)));
+		assert_eq!((metrics.dead_ends, metrics.junctions, metrics.branch_factor), (2, 0, 1.));
+	}
+
+	#[test]
+	fn solve_door_before_key() {
+		let room = solve_row("@ D K");
+		assert_eq!(solve_route(&room), None);
+		let metrics = solve_metrics(&room);
+		assert_eq!((metrics.solution, metrics.difficulty), (None, None));
+	}
+
+	#[test]
+	fn solve_gate_needs_its_key() {
+		assert_eq!(solve_route(&solve_row("k @ X D")), None); // A plain key doesn't open a red gate
+		// Turn, left, turn, then right through the red key and the gate to the door
+		assert_eq!(solve_metrics(&solve_row("k @ R X D")).solution, Some(7));
+	}
+
+	#[test]
+	fn solve_wrong_way_key() {
+		// Moving right passes over a leftward key, so go past, turn back for it, then turn again
+		let metrics = solve_metrics(&solve_row("@ k . D"));
+		assert_eq!((metrics.solution, metrics.backtracking, metrics.difficulty), (Some(7), 2, Some(9)));
+		assert_eq!(solve_metrics(&solve_row("@ K . D")).solution, Some(3));
+	}
+}
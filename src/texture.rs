@@ -1,10 +1,12 @@
 // Basic routines for working with textured quads
+// The sprite atlas itself is plain image data, so only upload needs the "graphics" feature
 
 use crate::constants::*;
 use seq_macro;
 use image::{GenericImage, GrayImage, ImageBuffer, Luma, imageops::{rotate90_in, rotate180_in, rotate270_in}};
 use rand::Rng;
 
+#[cfg(feature = "graphics")]
 const STANDARD_TEXTURE_DESCRIPTOR:wgpu::TextureDescriptor = wgpu::TextureDescriptor {
     size: wgpu::Extent3d {width:1,height:1,depth_or_array_layers:1},
     mip_level_count: 1,
@@ -99,6 +101,7 @@ impl FlexDecoder {
     }
 }
 
+#[cfg(feature = "graphics")]
 pub fn make_texture(device: &wgpu::Device, queue: &wgpu::Queue, img:GrayImage, label:&str) -> (wgpu::Texture, wgpu::TextureView) {
     let size = wgpu::Extent3d {width:img.width(), height:img.height(), ..STANDARD_TEXTURE_DESCRIPTOR.size};
     let texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -124,11 +127,12 @@ pub fn make_texture(device: &wgpu::Device, queue: &wgpu::Queue, img:GrayImage, l
     (texture, view)
 }
 
+#[cfg(feature = "graphics")]
 pub fn _make_sampler(device: &wgpu::Device) -> wgpu::Sampler { // Currently unused
     device.create_sampler(&wgpu::SamplerDescriptor::default())
 }
 
-pub async fn load_sprite_atlas() -> GrayImage {
+pub async fn load_sprite_atlas(rng:&mut impl Rng) -> GrayImage {
     let mut decoder = FlexDecoder::with_capacity(LARGEST_PNG_SIDE, LARGEST_PNG_SIDE);
 
     seq_macro::seq! { N in 0..8 {
@@ -152,14 +156,34 @@ pub async fn load_sprite_atlas() -> GrayImage {
         ];
     }};
 
-    let mut canvas = ImageBuffer::from_pixel(128, 32, Luma([0xFFu8])); //GrayImage::new(64, 32);
+    let mut canvas = ImageBuffer::from_pixel(128, KIND_Y_ORIGIN+ACTOR_SIDE, Luma([0xFFu8])); //GrayImage::new(64, 32);
 
+    let mut actor_img:Vec<GrayImage> = Vec::default();
     for idx in 0..8 {
         let img = decoder.load_from_memory(ACTOR[idx]).await.unwrap().to_luma8();
         canvas.copy_from(&img, (idx as u32)*ACTOR_SIDE, ACTOR_Y_ORIGIN).unwrap();
+        actor_img.push(img);
+    }
+
+    // Gate: bars with two crossbars, leaving the bottom row for pips
+    let mut gate_img = ImageBuffer::from_pixel(ACTOR_SIDE, ACTOR_SIDE, Luma([0xFFu8]));
+    for y in 0..7 {
+        for x in 1..7 {
+            if [1, 3, 4, 6].contains(&x) || y == 1 || y == 5 { gate_img.put_pixel(x, y, Luma([0])); }
+        }
+    }
+
+    // Keys and gates in every kind, marked by that many pips along the bottom (see actor_sprite)
+    for (slot, img) in [&actor_img[4], &actor_img[5], &gate_img].into_iter().enumerate() {
+        for kind in 0..KEY_KINDS {
+            let x = (slot as u32*KEY_KINDS + kind)*ACTOR_SIDE;
+            canvas.copy_from(img, x, KIND_Y_ORIGIN).unwrap();
+            for pip in 0..kind {
+                canvas.put_pixel(x + 1 + pip*2, KIND_Y_ORIGIN + ACTOR_SIDE - 1, Luma([0]));
+            }
+        }
     }
 
-    let mut rng = rand::thread_rng();
     for y in 0..6 {
         for x8 in 0..MONSTER_COUNT {
             for col in 1..4 {
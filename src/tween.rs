@@ -0,0 +1,52 @@
+// Sliding actors between cells. The game itself moves them a whole cell at a time; a Tween
+// remembers where everything was before a turn, and drawing eases from there to where it is now
+
+use crate::room::*;
+use crate::slots::*;
+
+use glam::{IVec2, Vec2};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tween {
+	pub from: Vec<(Handle, IVec2)>, // Every actor before the turn
+	pub start: f64, // Seconds, from tween_now
+	pub duration: f64,
+	pub progress: f32, // 0 at the old cells, 1 at the new ones
+}
+
+// Seconds since some fixed point, good for measuring intervals
+#[cfg(not(target_arch = "wasm32"))]
+pub fn tween_now() -> f64 {
+	use std::{sync::OnceLock, time::Instant};
+	static EPOCH:OnceLock<Instant> = OnceLock::new();
+	EPOCH.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
+// Instant panics on web, so ask javascript
+#[cfg(target_arch = "wasm32")]
+pub fn tween_now() -> f64 {
+	js_sys::Date::now()/1000.
+}
+
+// Call before the turn is taken
+pub fn tween_make(room:&Room, duration:f64) -> Tween {
+	let from = room.actors.iter().map(|(handle, &(_, at))| (handle, at)).collect();
+	Tween { from, start:tween_now(), duration, progress:0. }
+}
+
+// Catch up to the current time. Returns true if still moving, meaning another frame is wanted
+pub fn tween_step(tween:&mut Tween, now:f64) -> bool {
+	let linear = if tween.duration > 0. { ((now - tween.start)/tween.duration).clamp(0., 1.) as f32 } else { 1. };
+	tween.progress = linear*(2. - linear); // Ease out
+	linear < 1.
+}
+
+// Where to draw an actor, in cells. Anything that wasn't there before, or jumped more than
+// a cell (like stepping through a door into a new room), just appears
+pub fn tween_at(tween:&Tween, handle:Handle, at:IVec2) -> Vec2 {
+	match tween.from.iter().find(|&&(from_handle, _)| from_handle == handle) {
+		Some(&(_, from)) if (at - from).abs().max_element() <= 1 =>
+			from.as_vec2().lerp(at.as_vec2(), tween.progress),
+		_ => at.as_vec2()
+	}
+}